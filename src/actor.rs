@@ -1,7 +1,10 @@
-use sea_orm::{DatabaseConnection, QueryTrait};
+use sea_orm::{DatabaseConnection, DatabaseTransaction, QueryTrait};
 use tokio::sync::mpsc;
 
-use crate::carrier::execute::{ExecuteCarrier, ExecuteResult, ImplExecuteCarrier};
+use crate::{
+    carrier::execute::{ExecuteCarrier, ExecuteResult, ImplExecuteCarrier, TransactionFuture},
+    TablesCollector,
+};
 
 #[derive(Clone)]
 pub struct Actor {
@@ -93,4 +96,21 @@ impl ImplExecuteCarrier for Actor {
             );
         }
     }
+
+    fn transaction<F>(&mut self, transaction: F)
+    where
+        F: for<'c> FnOnce(
+                &'c DatabaseTransaction,
+                &'c mut TablesCollector,
+            ) -> TransactionFuture<'c>
+            + Send
+            + 'static,
+    {
+        ExecuteCarrier::transaction_static(
+            self.db.clone(),
+            self._bk_executing_sender.clone(),
+            self.all_tables.clone(),
+            transaction,
+        );
+    }
 }
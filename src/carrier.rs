@@ -1,4 +1,5 @@
 use execute::ExecuteCarrier;
+use joined_query::JoinedQueryCarrier;
 use manual_query::ManualQueryCarrier;
 use sea_orm::{DatabaseConnection, EntityTrait};
 use simple_query::SimpleQueryCarrier;
@@ -6,9 +7,13 @@ use tokio::sync::mpsc;
 
 use crate::messenger::ContainerData;
 
+use shared_query::SharedQueries;
+
 pub mod execute;
+pub mod joined_query;
 pub mod manual_query;
 pub mod query;
+pub mod shared_query;
 pub mod simple_query;
 
 pub(crate) fn both_simple_carriers<DbValue>(
@@ -17,6 +22,7 @@ pub(crate) fn both_simple_carriers<DbValue>(
     all_tables: Vec<String>,
     tables_changed_sender: mpsc::Sender<Vec<String>>,
     new_register_sender: mpsc::Sender<ContainerData>,
+    shared_queries: SharedQueries,
 ) -> (SimpleQueryCarrier<DbValue>, ExecuteCarrier)
 where
     DbValue: EntityTrait + Send + 'static,
@@ -27,6 +33,7 @@ where
         all_tables.clone(),
         tables_changed_sender.clone(),
         new_register_sender.clone(),
+        shared_queries,
     );
     let execute = ExecuteCarrier::register_new(
         name,
@@ -44,6 +51,7 @@ pub(crate) fn both_manual_carriers<DbValue>(
     all_tables: Vec<String>,
     tables_changed_sender: mpsc::Sender<Vec<String>>,
     new_register_sender: mpsc::Sender<ContainerData>,
+    shared_queries: SharedQueries,
 ) -> (ManualQueryCarrier<DbValue>, ExecuteCarrier)
 where
     DbValue: Send + 'static,
@@ -54,6 +62,37 @@ where
         all_tables.clone(),
         tables_changed_sender.clone(),
         new_register_sender.clone(),
+        shared_queries,
+    );
+    let execute = ExecuteCarrier::register_new(
+        name,
+        pool,
+        all_tables,
+        tables_changed_sender,
+        new_register_sender,
+    );
+    (query, execute)
+}
+
+pub(crate) fn both_joined_carriers<A, B>(
+    pool: DatabaseConnection,
+    name: String,
+    all_tables: Vec<String>,
+    tables_changed_sender: mpsc::Sender<Vec<String>>,
+    new_register_sender: mpsc::Sender<ContainerData>,
+    shared_queries: SharedQueries,
+) -> (JoinedQueryCarrier<A, B>, ExecuteCarrier)
+where
+    A: EntityTrait + Send + 'static,
+    B: EntityTrait + Send + 'static,
+{
+    let query = JoinedQueryCarrier::register_new(
+        name.clone(),
+        pool.clone(),
+        all_tables.clone(),
+        tables_changed_sender.clone(),
+        new_register_sender.clone(),
+        shared_queries,
     );
     let execute = ExecuteCarrier::register_new(
         name,
@@ -1,14 +1,27 @@
+pub mod foreign_keys;
+
 use core::panic;
-use std::collections::HashSet;
+use std::{collections::HashSet, fmt, future::Future, pin::Pin};
 
+use chrono::{DateTime, FixedOffset, Local};
 use sea_orm::{
-    ConnectionTrait, DatabaseConnection, DatabaseTransaction, DbErr, QueryTrait, Statement,
-    TransactionTrait,
+    AccessMode, ConnectionTrait, DatabaseConnection, DatabaseTransaction, DbErr, IsolationLevel,
+    QueryTrait, Statement, TransactionTrait,
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    task,
+};
+use tracing::{debug, error, warn};
+
+use crate::{
+    actor::Actor, consts::DB_BACKEND, get_tables_present, messenger::ContainerData, TablesCollector,
 };
-use tokio::{sync::mpsc, task};
-use tracing::{debug, error, Level};
+use foreign_keys::ForeignKeyViolation;
 
-use crate::{actor::Actor, consts::DB_BACKEND, get_tables_present, messenger::ContainerData};
+/// Future returned by the closure given to
+/// [transaction](ImplExecuteCarrier::transaction)
+pub type TransactionFuture<'c> = Pin<Box<dyn Future<Output = Result<(), DbErr>> + Send + 'c>>;
 
 pub(crate) struct ExecuteCarrier {
     pub(super) name: String,
@@ -18,6 +31,7 @@ pub(crate) struct ExecuteCarrier {
 
     executing_executes: mpsc::Receiver<ExecuteResult>,
     _bk_executing_sender: mpsc::Sender<ExecuteResult>,
+    last_error: Option<ExecuteError>,
 
     tables_changed_sender: mpsc::Sender<Vec<String>>,
     new_register_sender: mpsc::Sender<ContainerData>,
@@ -66,6 +80,7 @@ impl ExecuteCarrier {
             all_tables,
             executing_executes: reciver,
             _bk_executing_sender: sender,
+            last_error: None,
             tables_changed_sender,
             new_register_sender,
         }
@@ -76,19 +91,42 @@ impl ExecuteCarrier {
             let recived = self.executing_executes.try_recv();
             match recived {
                 Ok(Ok(affected_tables)) => {
+                    self.last_error = None;
                     let tables = affected_tables.clone();
                     let sender = self.tables_changed_sender.clone();
                     task::spawn(async move {
                         let _ = sender.send(tables).await;
                     });
                 }
-                Ok(Err(error)) => error!("{}: {error}", self.name),
+                Ok(Err(error)) => {
+                    match &error {
+                        ExecuteError::ForeignKeys(violations) => {
+                            for violation in violations {
+                                error!(
+                                    container = self.name,
+                                    table = violation.table,
+                                    row = violation.row,
+                                    parent = violation.parent,
+                                    constraint = violation.constraint,
+                                    "foreign key violation: {violation}"
+                                );
+                            }
+                        }
+                        error => error!("{}: {error}", self.name),
+                    }
+                    self.last_error = Some(error);
+                }
                 Err(mpsc::error::TryRecvError::Empty) => break,
                 _ => unreachable!(),
             }
         }
     }
 
+    /// The error of the last resolved execute, `None` if it succeeded
+    pub fn last_error(&self) -> Option<&ExecuteError> {
+        self.last_error.as_ref()
+    }
+
     pub fn execute(&mut self, execute: impl QueryTrait + Send + 'static) {
         Self::execute_static(
             self.name.clone(),
@@ -102,7 +140,7 @@ impl ExecuteCarrier {
     pub(crate) fn execute_static(
         name: String,
         db: DatabaseConnection,
-        sender: mpsc::Sender<Result<Vec<String>, DbErr>>,
+        sender: mpsc::Sender<ExecuteResult>,
         all_tables: &[String],
         execute: impl QueryTrait + Send + 'static,
     ) {
@@ -111,13 +149,42 @@ impl ExecuteCarrier {
 
         task::spawn(async move {
             assert!(!sender.is_closed());
-            let result = db.execute(execute).await.map(|_| tables);
+            let result = db
+                .execute(execute)
+                .await
+                .map(|_| tables)
+                .map_err(ExecuteError::from);
             if let Err(error) = sender.send(result).await {
                 panic!("{name}: {error}");
             }
         });
     }
 
+    /// Like [execute](Self::execute), but also reports when this execute
+    /// finished through the returned reciver, `None` if it failed. The error
+    /// itself is resolved like the ones of other executes and can be read
+    /// with [last_error](Self::last_error).
+    pub(crate) fn execute_awaited(
+        &mut self,
+        execute: impl QueryTrait + Send + 'static,
+    ) -> oneshot::Receiver<Option<DateTime<FixedOffset>>> {
+        let execute = execute.build(DB_BACKEND);
+        let tables = get_tables_present(&self.all_tables, &execute.to_string());
+        let db = self.db.clone();
+        let sender = self._bk_executing_sender.clone();
+        let (result_sender, result_reciver) = oneshot::channel();
+
+        task::spawn(async move {
+            let result = db.execute(execute).await;
+            let finished = result.is_ok().then(|| Local::now().into());
+            let _ = sender
+                .send(result.map(|_| tables).map_err(ExecuteError::from))
+                .await;
+            let _ = result_sender.send(finished);
+        });
+        result_reciver
+    }
+
     pub fn action<E>(&self) -> impl Fn(E)
     where
         E: QueryTrait + Send + 'static,
@@ -153,34 +220,59 @@ impl ExecuteCarrier {
     ) {
         let mut builder = TransactionBuilder::new(all_tables);
         transaction_builder(&mut builder);
-        let TransactionBuilder { executes, .. } = builder;
+        let TransactionBuilder {
+            steps,
+            isolation_level,
+            access_mode,
+            serialization_retries,
+            check_foreign_keys,
+            defer_constraints,
+            ..
+        } = builder;
+        let steps = steps.steps;
 
         task::spawn(async move {
             assert!(!sender.is_closed());
-            let transaction = async move || {
-                let txn = db.begin().await?;
-                //txn.execute_unprepared("PRAGMA defer_foreign_keys = true")
-                //    .await?;
+            let transaction = async |steps| {
+                let txn = db.begin_with_config(isolation_level, access_mode).await?;
+                if defer_constraints {
+                    foreign_keys::defer(&txn).await?;
+                }
 
                 let mut tables = HashSet::new();
-                for TransactionExecute {
-                    interested_tables,
-                    execute,
-                } in executes
-                {
-                    txn.execute(execute).await?;
-                    tables.extend(interested_tables);
+                if let Err(error) = run_steps(&txn, steps, &mut tables).await {
+                    return Err(match ForeignKeyViolation::from_error(&error) {
+                        Some(violation) if check_foreign_keys => {
+                            ExecuteError::ForeignKeys(vec![violation])
+                        }
+                        _ => ExecuteError::Db(error),
+                    });
                 }
 
-                if tracing::event_enabled!(Level::DEBUG) {
-                    log_foreign_key_check(&txn).await?;
+                if check_foreign_keys {
+                    let violations = foreign_keys::check(&txn, &tables).await?;
+                    if !violations.is_empty() {
+                        return Err(ExecuteError::ForeignKeys(violations));
+                    }
                 }
 
                 txn.commit().await?;
                 Ok(tables.into_iter().collect::<Vec<_>>())
             };
 
-            let transaction_result = transaction().await;
+            let mut retries = 0;
+            let transaction_result = loop {
+                let result = transaction(steps.clone()).await;
+                match result {
+                    Err(ExecuteError::Db(error))
+                        if retries < serialization_retries && is_serialization_failure(&error) =>
+                    {
+                        retries += 1;
+                        debug!("retrying transaction after serialization failure {retries}");
+                    }
+                    result => break result,
+                }
+            };
             if let Err(send_error) = sender.send(transaction_result).await {
                 panic!("{send_error}");
             }
@@ -202,24 +294,198 @@ impl ExecuteCarrier {
             Self::execute_many_static(db, sender, &all_tables, transaction_builder);
         }
     }
+
+    pub fn transaction<F>(&self, transaction: F)
+    where
+        F: for<'c> FnOnce(
+                &'c DatabaseTransaction,
+                &'c mut TablesCollector,
+            ) -> TransactionFuture<'c>
+            + Send
+            + 'static,
+    {
+        Self::transaction_static(
+            self.db.clone(),
+            self._bk_executing_sender.clone(),
+            self.all_tables.clone(),
+            transaction,
+        );
+    }
+
+    pub(crate) fn transaction_static<F>(
+        db: DatabaseConnection,
+        sender: mpsc::Sender<ExecuteResult>,
+        all_tables: Vec<String>,
+        transaction: F,
+    ) where
+        F: for<'c> FnOnce(
+                &'c DatabaseTransaction,
+                &'c mut TablesCollector,
+            ) -> TransactionFuture<'c>
+            + Send
+            + 'static,
+    {
+        task::spawn(async move {
+            assert!(!sender.is_closed());
+            let mut collector = TablesCollector::new(all_tables);
+            let transaction_result = async {
+                let txn = db.begin().await?;
+                transaction(&txn, &mut collector).await?;
+                txn.commit().await?;
+                Ok(collector.tables.into_iter().collect::<Vec<_>>())
+            }
+            .await;
+
+            if let Err(send_error) = sender.send(transaction_result).await {
+                panic!("{send_error}");
+            }
+        });
+    }
 }
 
+/// Collects the statements of one transaction. Statements run in the order
+/// they were added, parts of the transaction can be wrapped in a
+/// [savepoint](TransactionBuilder::savepoint) to roll back only that part.
 pub struct TransactionBuilder<'executor> {
-    executes: Vec<TransactionExecute>,
-    all_tables: &'executor [String],
+    steps: SavepointBuilder<'executor>,
+    isolation_level: Option<IsolationLevel>,
+    access_mode: Option<AccessMode>,
+    serialization_retries: u32,
+    check_foreign_keys: bool,
+    defer_constraints: bool,
 }
 
 impl<'executor> TransactionBuilder<'executor> {
     fn new(all_tables: &'executor [String]) -> Self {
         Self {
-            executes: vec![],
+            steps: SavepointBuilder::new(all_tables),
+            isolation_level: None,
+            access_mode: None,
+            serialization_retries: DEFAULT_SERIALIZATION_RETRIES,
+            check_foreign_keys: false,
+            defer_constraints: false,
+        }
+    }
+
+    /// Sets the isolation level of the transaction, the database default is
+    /// used otherwise.
+    pub fn isolation_level(&mut self, isolation_level: IsolationLevel) -> &mut Self {
+        self.isolation_level = Some(isolation_level);
+        self
+    }
+
+    /// Sets the access mode of the transaction, for example
+    /// [AccessMode::ReadOnly] for consistent snapshots.
+    pub fn access_mode(&mut self, access_mode: AccessMode) -> &mut Self {
+        self.access_mode = Some(access_mode);
+        self
+    }
+
+    /// How often the whole transaction is run again when Postgres aborts it
+    /// with a serialization failure, which can happen with
+    /// [IsolationLevel::Serializable] and [IsolationLevel::RepeatableRead].
+    /// Defaults to 3, other backends never retry.
+    pub fn serialization_retries(&mut self, retries: u32) -> &mut Self {
+        self.serialization_retries = retries;
+        self
+    }
+
+    /// Checks the foreign keys of the changed tables before commiting and
+    /// fails the transaction with [ExecuteError::ForeignKeys] listing the
+    /// violations, which can be read with
+    /// [last_execute_error](ImplExecuteError::last_execute_error). See
+    /// [foreign_keys::check](foreign_keys) for what each backend reports.
+    pub fn check_foreign_keys(&mut self) -> &mut Self {
+        self.check_foreign_keys = true;
+        self
+    }
+
+    /// Checks the foreign keys only when commiting instead of after every
+    /// statement, so that rows referencing each other can be inserted in any
+    /// order, see [foreign_keys::defer](foreign_keys) for the supported
+    /// backends.
+    pub fn defer_constraints(&mut self) -> &mut Self {
+        self.defer_constraints = true;
+        self
+    }
+
+    pub fn execute(&mut self, execute: impl QueryTrait + Send + 'static) -> &mut Self {
+        self.steps.execute(execute);
+        self
+    }
+
+    /// See [SavepointBuilder::try_execute]
+    pub fn try_execute(&mut self, execute: impl QueryTrait + Send + 'static) -> &mut Self {
+        self.steps.try_execute(execute);
+        self
+    }
+
+    /// See [SavepointBuilder::savepoint]
+    pub fn savepoint(
+        &mut self,
+        on_error: OnError,
+        savepoint_builder: impl FnOnce(&mut SavepointBuilder),
+    ) -> &mut Self {
+        self.steps.savepoint(on_error, savepoint_builder);
+        self
+    }
+
+    pub fn execute_many<Q>(&mut self, execute_iter: impl IntoIterator<Item = Q>) -> &mut Self
+    where
+        Q: QueryTrait + Send + 'static,
+    {
+        self.steps.execute_many(execute_iter);
+        self
+    }
+}
+
+/// Collects the statements of a [savepoint](TransactionBuilder::savepoint).
+/// Only offers the steps, the options of the transaction can only be set on
+/// the [TransactionBuilder] itself.
+pub struct SavepointBuilder<'executor> {
+    steps: Vec<TransactionStep>,
+    all_tables: &'executor [String],
+}
+
+impl<'executor> SavepointBuilder<'executor> {
+    fn new(all_tables: &'executor [String]) -> Self {
+        Self {
+            steps: vec![],
             all_tables,
         }
     }
 
     pub fn execute(&mut self, execute: impl QueryTrait + Send + 'static) -> &mut Self {
         let transaction_execute = TransactionExecute::from_execute(execute, self.all_tables);
-        self.executes.push(transaction_execute);
+        self.steps
+            .push(TransactionStep::Execute(transaction_execute));
+        self
+    }
+
+    /// Executes the statement in its own savepoint and skips it if it fails,
+    /// short for [savepoint](Self::savepoint) with [OnError::Skip]
+    pub fn try_execute(&mut self, execute: impl QueryTrait + Send + 'static) -> &mut Self {
+        self.savepoint(OnError::Skip, |builder| {
+            builder.execute(execute);
+        })
+    }
+
+    /// Runs the statements added in `savepoint_builder` inside a savepoint,
+    /// which can be nested. If one of them fails, everything since the
+    /// savepoint is rolled back and `on_error` decides whether the whole
+    /// transaction is aborted or the transaction continues without this step.
+    /// Only tables changed by steps that were kept are invalidated.
+    pub fn savepoint(
+        &mut self,
+        on_error: OnError,
+        savepoint_builder: impl FnOnce(&mut SavepointBuilder),
+    ) -> &mut Self {
+        let mut builder = SavepointBuilder::new(self.all_tables);
+        savepoint_builder(&mut builder);
+        self.steps.push(TransactionStep::Savepoint {
+            steps: builder.steps,
+            on_error,
+        });
         self
     }
 
@@ -227,14 +493,78 @@ impl<'executor> TransactionBuilder<'executor> {
     where
         Q: QueryTrait + Send + 'static,
     {
-        let queries = execute_iter
-            .into_iter()
-            .map(|q| TransactionExecute::from_execute(q, self.all_tables));
-        self.executes.extend(queries);
+        let queries = execute_iter.into_iter().map(|q| {
+            TransactionStep::Execute(TransactionExecute::from_execute(q, self.all_tables))
+        });
+        self.steps.extend(queries);
         self
     }
 }
 
+/// What happens when a statement inside a
+/// [savepoint](TransactionBuilder::savepoint) fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnError {
+    /// Rolls back the whole transaction
+    #[default]
+    Abort,
+    /// Rolls back to the savepoint and continues with the following steps
+    Skip,
+}
+
+#[derive(Clone)]
+enum TransactionStep {
+    Execute(TransactionExecute),
+    Savepoint {
+        steps: Vec<TransactionStep>,
+        on_error: OnError,
+    },
+}
+
+/// Runs the steps in `txn` and adds the tables of all kept steps to `tables`
+fn run_steps<'a>(
+    txn: &'a DatabaseTransaction,
+    steps: Vec<TransactionStep>,
+    tables: &'a mut HashSet<String>,
+) -> Pin<Box<dyn Future<Output = Result<(), DbErr>> + Send + 'a>> {
+    Box::pin(async move {
+        for step in steps {
+            match step {
+                TransactionStep::Execute(TransactionExecute {
+                    interested_tables,
+                    execute,
+                }) => {
+                    txn.execute(execute).await?;
+                    tables.extend(interested_tables);
+                }
+                TransactionStep::Savepoint { steps, on_error } => {
+                    let savepoint = txn.begin().await?;
+                    let mut savepoint_tables = HashSet::new();
+                    match run_steps(&savepoint, steps, &mut savepoint_tables).await {
+                        Ok(()) => {
+                            savepoint.commit().await?;
+                            tables.extend(savepoint_tables);
+                        }
+                        Err(error) => {
+                            savepoint.rollback().await?;
+                            match on_error {
+                                OnError::Abort => return Err(error),
+                                // the transaction has to be retried as a whole
+                                OnError::Skip if is_serialization_failure(&error) => {
+                                    return Err(error)
+                                }
+                                OnError::Skip => warn!("skipped savepoint: {error}"),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+#[derive(Clone)]
 struct TransactionExecute {
     interested_tables: Vec<String>,
     execute: Statement,
@@ -251,7 +581,58 @@ impl TransactionExecute {
     }
 }
 
-pub(crate) type ExecuteResult = Result<Vec<String>, DbErr>;
+const DEFAULT_SERIALIZATION_RETRIES: u32 = 3;
+
+/// Postgres reports conflicting concurrent transactions with SQLSTATE 40001
+#[cfg(feature = "psql")]
+fn is_serialization_failure(error: &DbErr) -> bool {
+    use sea_orm::{sqlx, RuntimeErr};
+
+    match error {
+        DbErr::Exec(RuntimeErr::SqlxError(sqlx::Error::Database(error)))
+        | DbErr::Query(RuntimeErr::SqlxError(sqlx::Error::Database(error))) => {
+            error.code().as_deref() == Some("40001")
+        }
+        _ => false,
+    }
+}
+
+#[cfg(not(feature = "psql"))]
+fn is_serialization_failure(_error: &DbErr) -> bool {
+    false
+}
+
+/// Why an execute or transaction failed
+#[derive(Debug)]
+pub enum ExecuteError {
+    Db(DbErr),
+    /// Only returned when the foreign keys are
+    /// [checked](TransactionBuilder::check_foreign_keys)
+    ForeignKeys(Vec<ForeignKeyViolation>),
+}
+
+impl fmt::Display for ExecuteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Db(error) => write!(f, "{error}"),
+            Self::ForeignKeys(violations) => {
+                write!(f, "foreign key violations: ")?;
+                let messages = violations.iter().map(|v| v.message.as_str());
+                write!(f, "{}", messages.collect::<Vec<_>>().join("; "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExecuteError {}
+
+impl From<DbErr> for ExecuteError {
+    fn from(error: DbErr) -> Self {
+        Self::Db(error)
+    }
+}
+
+pub(crate) type ExecuteResult = Result<Vec<String>, ExecuteError>;
 
 pub trait ImplExecuteCarrier {
     fn actor(&self) -> Actor;
@@ -263,6 +644,20 @@ pub trait ImplExecuteCarrier {
     fn many_action<B>(&self) -> impl Fn(B)
     where
         B: FnOnce(&mut TransactionBuilder);
+    /// Runs `transaction` in a transaction in the background, so that it can
+    /// read and decide on the following statements. Statements have to be
+    /// executed through [TablesCollector::execute] so that the changed tables
+    /// are invalidated once the transaction is commited, statements executed
+    /// on the transaction directly are not tracked and their tables are not
+    /// invalidated. Returning an error rolls back the transaction.
+    fn transaction<F>(&mut self, transaction: F)
+    where
+        F: for<'c> FnOnce(
+                &'c DatabaseTransaction,
+                &'c mut TablesCollector,
+            ) -> TransactionFuture<'c>
+            + Send
+            + 'static;
 }
 
 impl<T> ImplExecuteCarrier for T
@@ -298,6 +693,35 @@ where
     {
         self.ref_execute_carrier().many_action()
     }
+
+    fn transaction<F>(&mut self, transaction: F)
+    where
+        F: for<'c> FnOnce(
+                &'c DatabaseTransaction,
+                &'c mut TablesCollector,
+            ) -> TransactionFuture<'c>
+            + Send
+            + 'static,
+    {
+        self.ref_execute_carrier().transaction(transaction);
+    }
+}
+
+/// Read access to the outcome of the executes of a container. Not offered by
+/// [Actor], whose results are resolved by the container it was created from.
+pub trait ImplExecuteError {
+    /// The error of the last execute, transaction or action that finished,
+    /// `None` if it succeeded. Updated by the `state_update` of the container.
+    fn last_execute_error(&self) -> Option<&ExecuteError>;
+}
+
+impl<T> ImplExecuteError for T
+where
+    T: HasExecuteCarrier,
+{
+    fn last_execute_error(&self) -> Option<&ExecuteError> {
+        self.ref_execute_carrier().last_error()
+    }
 }
 
 pub(crate) trait HasExecuteCarrier {
@@ -305,43 +729,334 @@ pub(crate) trait HasExecuteCarrier {
     fn ref_mut_execute_carrier(&mut self) -> &mut ExecuteCarrier;
 }
 
-async fn log_foreign_key_check(txn: &DatabaseTransaction) -> Result<(), DbErr> {
-    let res = txn
-        .query_all(Statement::from_string(
-            DB_BACKEND,
-            "PRAGMA foreign_key_check;",
-        ))
-        .await?;
-
-    if res.is_empty() {
-        debug!("There are no FK issues");
-        return Ok(());
-    }
-
-    debug!(
-        r#"Result of checking FK's:
-{}
-{}"#,
-        res.first()
-            .map(|r| r.column_names().join("|"))
-            .unwrap_or_default(),
-        res.iter()
-            .map(|r| {
-                r.column_names()
-                    .into_iter()
-                    .map(
-                        |col| match (r.try_get::<String>("", &col), r.try_get::<i32>("", &col)) {
-                            (Ok(str_val), Err(_)) => str_val,
-                            (Err(_), Ok(int_val)) => int_val.to_string(),
-                            (Err(err1), Err(err2)) => unreachable!("{err1}{err2}"),
-                            _ => unreachable!(),
-                        },
-                    )
-                    .collect::<Vec<_>>()
-                    .join("|")
-            })
-            .collect::<Vec<_>>()
-            .join("\n")
-    );
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use sea_orm::{DbBackend, DbErr, EntityTrait, ProxyExecResult, QueryTrait};
+    use tokio::sync::mpsc;
+
+    use super::{ExecuteCarrier, ExecuteError, ExecuteResult, OnError, TransactionBuilder};
+    use crate::{
+        consts::DB_BACKEND,
+        test_db::{self, all_tables, project, task, FakeDatabase},
+    };
+
+    fn carrier(fake: &Arc<FakeDatabase>) -> ExecuteCarrier {
+        let (tables_changed_sender, _) = mpsc::channel(10);
+        let (new_register_sender, _) = mpsc::channel(10);
+        ExecuteCarrier::register_new(
+            "test".to_string(),
+            test_db::connect(fake),
+            all_tables(),
+            tables_changed_sender,
+            new_register_sender,
+        )
+    }
+
+    async fn resolve(carrier: &mut ExecuteCarrier) {
+        while carrier.executing_executes.is_empty() {
+            tokio::task::yield_now().await;
+        }
+        carrier.try_resolve_executes();
+    }
+
+    async fn run_transaction(fake: &Arc<FakeDatabase>) -> ExecuteResult {
+        let (sender, mut reciver) = mpsc::channel(1);
+        ExecuteCarrier::transaction_static(
+            test_db::connect(fake),
+            sender,
+            all_tables(),
+            |txn, collector| {
+                Box::pin(async move {
+                    let _ = collector.execute(txn, project::Entity::delete_many()).await;
+                    collector.execute(txn, task::Entity::delete_many()).await?;
+                    Ok(())
+                })
+            },
+        );
+        reciver.recv().await.unwrap()
+    }
+
+    async fn run_many(
+        fake: &Arc<FakeDatabase>,
+        transaction_builder: impl FnOnce(&mut TransactionBuilder),
+    ) -> ExecuteResult {
+        let (sender, mut reciver) = mpsc::channel(1);
+        ExecuteCarrier::execute_many_static(
+            test_db::connect(fake),
+            sender,
+            &all_tables(),
+            transaction_builder,
+        );
+        reciver.recv().await.unwrap()
+    }
+
+    fn failure() -> DbErr {
+        DbErr::Custom("constraint".to_string())
+    }
+
+    #[tokio::test]
+    async fn transaction_invalidates_executed_tables() {
+        let fake = Arc::new(FakeDatabase::default());
+        let mut tables = run_transaction(&fake).await.unwrap();
+        tables.sort();
+        assert_eq!(tables, all_tables());
+    }
+
+    #[tokio::test]
+    async fn failed_statement_is_not_invalidated() {
+        let fake = Arc::new(FakeDatabase::default());
+        fake.push_execute(Err(failure()));
+        let tables = run_transaction(&fake).await.unwrap();
+        assert_eq!(tables, vec![all_tables()[1].clone()]);
+    }
+
+    #[tokio::test]
+    async fn last_error_is_kept_until_an_execute_succeeds() {
+        let fake = Arc::new(FakeDatabase::default());
+        let mut carrier = carrier(&fake);
+        fake.push_execute(Err(failure()));
+
+        carrier.execute(task::Entity::delete_many());
+        resolve(&mut carrier).await;
+        assert!(matches!(carrier.last_error(), Some(ExecuteError::Db(_))));
+
+        carrier.execute(task::Entity::delete_many());
+        resolve(&mut carrier).await;
+        assert!(carrier.last_error().is_none());
+    }
+
+    #[tokio::test]
+    async fn foreign_keys_are_checked_for_changed_tables() {
+        let fake = Arc::new(FakeDatabase::default());
+        let mut carrier = carrier(&fake);
+        carrier.execute_many(|builder| {
+            builder
+                .check_foreign_keys()
+                .execute(task::Entity::delete_many());
+        });
+        resolve(&mut carrier).await;
+
+        assert!(carrier.last_error().is_none());
+        let checks = fake
+            .statements()
+            .into_iter()
+            .filter(|statement| statement.starts_with("PRAGMA foreign_key_check"))
+            .collect::<Vec<_>>();
+        let expected = match DB_BACKEND {
+            DbBackend::Sqlite => vec![format!("PRAGMA foreign_key_check({});", all_tables()[1])],
+            _ => vec![],
+        };
+        assert_eq!(checks, expected);
+    }
+
+    #[tokio::test]
+    async fn constraints_are_deferred_before_the_statements() {
+        let fake = Arc::new(FakeDatabase::default());
+        run_many(&fake, |builder| {
+            builder
+                .defer_constraints()
+                .execute(task::Entity::delete_many())
+                .execute(project::Entity::delete_many());
+        })
+        .await
+        .unwrap();
+
+        let mut expected = match DB_BACKEND {
+            DbBackend::Sqlite => vec!["PRAGMA defer_foreign_keys = true".to_string()],
+            DbBackend::Postgres => vec!["SET CONSTRAINTS ALL DEFERRED".to_string()],
+            DbBackend::MySql => vec![],
+        };
+        expected.extend([
+            task::Entity::delete_many().build(DB_BACKEND).to_string(),
+            project::Entity::delete_many().build(DB_BACKEND).to_string(),
+        ]);
+        assert_eq!(fake.statements(), expected);
+    }
+
+    #[tokio::test]
+    async fn skipped_savepoint_continues_the_transaction() {
+        let fake = Arc::new(FakeDatabase::default());
+        fake.push_execute(Ok(ProxyExecResult::new(0, 1)));
+        fake.push_execute(Err(failure()));
+        let tables = run_many(&fake, |builder| {
+            builder
+                .execute(task::Entity::delete_many())
+                .savepoint(OnError::Skip, |builder| {
+                    builder.execute(project::Entity::delete_many());
+                })
+                .execute(task::Entity::delete_many());
+        })
+        .await
+        .unwrap();
+        assert_eq!(tables, vec![all_tables()[1].clone()]);
+        assert_eq!(fake.statements().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn failed_savepoint_with_abort_fails_the_transaction() {
+        let fake = Arc::new(FakeDatabase::default());
+        fake.push_execute(Err(failure()));
+        let result = run_many(&fake, |builder| {
+            builder
+                .savepoint(OnError::Abort, |builder| {
+                    builder.execute(project::Entity::delete_many());
+                })
+                .execute(task::Entity::delete_many());
+        })
+        .await;
+        assert!(matches!(result, Err(ExecuteError::Db(DbErr::Custom(_)))));
+        assert_eq!(fake.statements().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn nested_savepoint_aborts_up_to_the_skipping_one() {
+        let fake = Arc::new(FakeDatabase::default());
+        fake.push_execute(Ok(ProxyExecResult::new(0, 1)));
+        fake.push_execute(Err(failure()));
+        let tables = run_many(&fake, |builder| {
+            builder
+                .savepoint(OnError::Skip, |builder| {
+                    builder
+                        .execute(project::Entity::delete_many())
+                        .savepoint(OnError::Abort, |builder| {
+                            builder.execute(task::Entity::delete_many());
+                        })
+                        // not run, the outer savepoint is already rolled back
+                        .execute(project::Entity::delete_many());
+                })
+                .execute(task::Entity::delete_many());
+        })
+        .await
+        .unwrap();
+        assert_eq!(tables, vec![all_tables()[1].clone()]);
+        assert_eq!(fake.statements().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn nested_savepoint_is_skipped_alone() {
+        let fake = Arc::new(FakeDatabase::default());
+        fake.push_execute(Ok(ProxyExecResult::new(0, 1)));
+        fake.push_execute(Err(failure()));
+        let tables = run_many(&fake, |builder| {
+            builder.savepoint(OnError::Abort, |builder| {
+                builder
+                    .execute(project::Entity::delete_many())
+                    .try_execute(task::Entity::delete_many());
+            });
+        })
+        .await
+        .unwrap();
+        assert_eq!(tables, vec![all_tables()[0].clone()]);
+    }
+
+    #[tokio::test]
+    async fn only_tables_of_kept_steps_are_invalidated() {
+        let fake = Arc::new(FakeDatabase::default());
+        fake.push_execute(Err(failure()));
+        let tables = run_many(&fake, |builder| {
+            builder
+                .try_execute(project::Entity::delete_many())
+                .try_execute(task::Entity::delete_many());
+        })
+        .await
+        .unwrap();
+        assert_eq!(tables, vec![all_tables()[1].clone()]);
+    }
+
+    /// A Postgres error with the SQLSTATE of a serialization failure
+    #[cfg(feature = "psql")]
+    fn serialization_failure() -> DbErr {
+        use std::{borrow::Cow, error::Error, fmt};
+
+        use sea_orm::{
+            sqlx::{self, error::DatabaseError, error::ErrorKind},
+            RuntimeErr,
+        };
+
+        #[derive(Debug)]
+        struct SerializationFailure;
+
+        impl fmt::Display for SerializationFailure {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "could not serialize access")
+            }
+        }
+
+        impl Error for SerializationFailure {}
+
+        impl DatabaseError for SerializationFailure {
+            fn message(&self) -> &str {
+                "could not serialize access"
+            }
+            fn code(&self) -> Option<Cow<'_, str>> {
+                Some(Cow::Borrowed("40001"))
+            }
+            fn as_error(&self) -> &(dyn Error + Send + Sync + 'static) {
+                self
+            }
+            fn as_error_mut(&mut self) -> &mut (dyn Error + Send + Sync + 'static) {
+                self
+            }
+            fn into_error(self: Box<Self>) -> Box<dyn Error + Send + Sync + 'static> {
+                self
+            }
+            fn kind(&self) -> ErrorKind {
+                ErrorKind::Other
+            }
+        }
+
+        let error = sqlx::Error::Database(Box::new(SerializationFailure));
+        DbErr::Exec(RuntimeErr::SqlxError(error))
+    }
+
+    #[cfg(feature = "psql")]
+    #[tokio::test]
+    async fn serialization_failure_retries_the_transaction() {
+        let fake = Arc::new(FakeDatabase::default());
+        fake.push_execute(Err(serialization_failure()));
+        let tables = run_many(&fake, |builder| {
+            builder.execute(task::Entity::delete_many());
+        })
+        .await
+        .unwrap();
+        assert_eq!(tables, vec![all_tables()[1].clone()]);
+        assert_eq!(fake.statements().len(), 2);
+    }
+
+    #[cfg(feature = "psql")]
+    #[tokio::test]
+    async fn serialization_retries_are_limited() {
+        let fake = Arc::new(FakeDatabase::default());
+        for _ in 0..3 {
+            fake.push_execute(Err(serialization_failure()));
+        }
+        let result = run_many(&fake, |builder| {
+            builder
+                .serialization_retries(2)
+                .execute(task::Entity::delete_many());
+        })
+        .await;
+        assert!(matches!(result, Err(ExecuteError::Db(_))));
+        assert_eq!(fake.statements().len(), 3);
+    }
+
+    #[cfg(feature = "psql")]
+    #[tokio::test]
+    async fn serialization_failure_in_skipped_savepoint_retries_the_transaction() {
+        let fake = Arc::new(FakeDatabase::default());
+        fake.push_execute(Ok(ProxyExecResult::new(0, 1)));
+        fake.push_execute(Err(serialization_failure()));
+        let mut tables = run_many(&fake, |builder| {
+            builder
+                .execute(project::Entity::delete_many())
+                .try_execute(task::Entity::delete_many());
+        })
+        .await
+        .unwrap();
+        tables.sort();
+        assert_eq!(tables, all_tables());
+        assert_eq!(fake.statements().len(), 4);
+    }
 }
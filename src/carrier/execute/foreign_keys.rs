@@ -0,0 +1,129 @@
+use std::{collections::HashSet, fmt};
+
+use sea_orm::{
+    sqlx, ConnectionTrait, DatabaseTransaction, DbBackend, DbErr, RuntimeErr, SqlErr, Statement,
+};
+
+use tracing::warn;
+
+use crate::consts::DB_BACKEND;
+
+/// A foreign key that points to a row that doesn't exist, found by
+/// [TransactionBuilder::check_foreign_keys](super::TransactionBuilder::check_foreign_keys).
+/// Which fields are known depends on the backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKeyViolation {
+    /// The table containing the foreign key
+    pub table: Option<String>,
+    /// The rowid of the violating row, only known on SQLite
+    pub row: Option<i64>,
+    /// The referenced table, only known on SQLite
+    pub parent: Option<String>,
+    pub constraint: Option<String>,
+    /// The message of the database
+    pub message: String,
+}
+
+impl fmt::Display for ForeignKeyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl ForeignKeyViolation {
+    /// Reads the violation from the error of a failed statement
+    pub(super) fn from_error(error: &DbErr) -> Option<Self> {
+        let Some(SqlErr::ForeignKeyConstraintViolation(message)) = error.sql_err() else {
+            return None;
+        };
+        let (table, constraint) = match error {
+            DbErr::Exec(RuntimeErr::SqlxError(sqlx::Error::Database(error)))
+            | DbErr::Query(RuntimeErr::SqlxError(sqlx::Error::Database(error))) => (
+                error.table().map(ToOwned::to_owned),
+                error.constraint().map(ToOwned::to_owned),
+            ),
+            _ => (None, None),
+        };
+        Some(Self {
+            table,
+            row: None,
+            parent: None,
+            constraint,
+            message,
+        })
+    }
+}
+
+/// Defers the foreign key checks of `txn` until it is commited, with
+/// `PRAGMA defer_foreign_keys` on SQLite and `SET CONSTRAINTS ALL DEFERRED` on
+/// Postgres, where only constraints declared as `DEFERRABLE` are deferred.
+/// MySQL can't defer its checks, so only a warning is logged there and the
+/// checks still run after every statement.
+pub(super) async fn defer(txn: &DatabaseTransaction) -> Result<(), DbErr> {
+    let statement = match DB_BACKEND {
+        DbBackend::Sqlite => "PRAGMA defer_foreign_keys = true",
+        DbBackend::Postgres => "SET CONSTRAINTS ALL DEFERRED",
+        DbBackend::MySql => {
+            warn!("MySQL can't defer foreign key checks, they still run after every statement");
+            return Ok(());
+        }
+    };
+    txn.execute_unprepared(statement).await?;
+    Ok(())
+}
+
+/// Checks the foreign keys of `tables`, the tables changed in `txn`, before it
+/// is commited.
+///
+/// SQLite lists all violations in these tables with `PRAGMA
+/// foreign_key_check`, rows of other tables that lost their parent are only
+/// reported by the failing commit. Postgres checks its deferred constraints
+/// early with `SET CONSTRAINTS ALL IMMEDIATE` and reports the first violation.
+/// MySQL checks every statement right away, so the violation is already
+/// reported by the failing statement.
+pub(super) async fn check(
+    txn: &DatabaseTransaction,
+    tables: &HashSet<String>,
+) -> Result<Vec<ForeignKeyViolation>, DbErr> {
+    match DB_BACKEND {
+        DbBackend::Sqlite => {
+            let mut rows = vec![];
+            for table in tables {
+                let statement = format!("PRAGMA foreign_key_check({table});");
+                rows.extend(
+                    txn.query_all(Statement::from_string(DB_BACKEND, statement))
+                        .await?,
+                );
+            }
+            rows.into_iter()
+                .map(|row| {
+                    let table = row.try_get::<String>("", "table")?;
+                    let rowid = row.try_get::<Option<i64>>("", "rowid")?;
+                    let parent = row.try_get::<String>("", "parent")?;
+                    let fkid = row.try_get::<i64>("", "fkid")?;
+                    Ok(ForeignKeyViolation {
+                        message: format!(
+                            "row {} of {table} references a missing row of {parent}",
+                            rowid.map_or("without rowid".into(), |rowid| rowid.to_string())
+                        ),
+                        table: Some(table),
+                        row: rowid,
+                        parent: Some(parent),
+                        constraint: Some(fkid.to_string()),
+                    })
+                })
+                .collect()
+        }
+        DbBackend::Postgres => match txn
+            .execute_unprepared("SET CONSTRAINTS ALL IMMEDIATE")
+            .await
+        {
+            Ok(_) => Ok(vec![]),
+            Err(error) => match ForeignKeyViolation::from_error(&error) {
+                Some(violation) => Ok(vec![violation]),
+                None => Err(error),
+            },
+        },
+        DbBackend::MySql => Ok(vec![]),
+    }
+}
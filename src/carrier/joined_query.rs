@@ -0,0 +1,303 @@
+use chrono::Duration;
+use sea_orm::{
+    sea_query::SelectStatement, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait,
+    QueryResult, QueryTrait, SelectTwo, SelectTwoMany, SelectTwoModel, SelectorTrait, Statement,
+};
+use tokio::sync::mpsc;
+
+use crate::{container::builder::ContainerBuilder, messenger::ContainerData};
+
+use super::{
+    query::{HasQueryCarrier, ImplQueryCarrier, PreparedQuery, QueryCarrier},
+    shared_query::SharedQueries,
+};
+
+/// A row of `A` together with the related row of `B`, if there is one
+pub type Joined<A, B> = (<A as EntityTrait>::Model, Option<<B as EntityTrait>::Model>);
+
+/// Selects of two entities whose rows can be read as [Joined] values, meaning
+/// [SelectTwo] from `find_also_related` and [SelectTwoMany] from
+/// `find_with_related`.
+pub trait JoinedSelect<A, B>: QueryTrait<QueryStatement = SelectStatement>
+where
+    A: EntityTrait,
+    B: EntityTrait,
+{
+}
+
+impl<A, B> JoinedSelect<A, B> for SelectTwo<A, B>
+where
+    A: EntityTrait,
+    B: EntityTrait,
+{
+}
+
+impl<A, B> JoinedSelect<A, B> for SelectTwoMany<A, B>
+where
+    A: EntityTrait,
+    B: EntityTrait,
+{
+}
+
+#[derive(Clone)]
+pub struct JoinedQueryCarrier<A, B>
+where
+    A: EntityTrait + Send + 'static,
+    B: EntityTrait + Send + 'static,
+{
+    carrier: QueryCarrier<Joined<A, B>>,
+}
+
+impl<A, B> JoinedQueryCarrier<A, B>
+where
+    A: EntityTrait + Send + 'static,
+    B: EntityTrait + Send + 'static,
+{
+    pub fn new(carrier: QueryCarrier<Joined<A, B>>) -> Self {
+        Self { carrier }
+    }
+
+    pub fn register_new(
+        name: String,
+        pool: DatabaseConnection,
+        all_tables: Vec<String>,
+        tables_changed_sender: mpsc::Sender<Vec<String>>,
+        new_register_sender: mpsc::Sender<ContainerData>,
+        shared_queries: SharedQueries,
+    ) -> Self {
+        Self::new(QueryCarrier::register_new(
+            name,
+            pool,
+            all_tables,
+            tables_changed_sender,
+            new_register_sender,
+            shared_queries,
+        ))
+    }
+
+    pub fn builder(&self) -> ContainerBuilder {
+        self.carrier.builder()
+    }
+}
+
+impl<A, B> JoinedQueryCarrier<A, B>
+where
+    A: EntityTrait + Send + 'static,
+    B: EntityTrait + Send + 'static,
+    A::Model: Sync,
+    B::Model: Sync,
+{
+    pub fn query(&mut self, query: impl JoinedSelect<A, B>) {
+        self.carrier.query(query, Self::run);
+    }
+
+    fn run(carrier: &mut QueryCarrier<Joined<A, B>>, prepared: &PreparedQuery) {
+        carrier.run(prepared, |db, statement| async move {
+            query_joined::<A, B>(&db, statement).await
+        });
+    }
+
+    /// Does the query once and then stores it internally to redo later
+    pub fn stored_query(&mut self, query: impl JoinedSelect<A, B>) {
+        self.carrier.stored_query(query, Self::run);
+    }
+
+    /// Reruns the stored query with its already built statement, does
+    /// nothing if no query was stored
+    pub fn rerun_stored_query(&mut self) {
+        self.carrier.rerun_stored_query(Self::run);
+    }
+}
+
+pub trait ImplJoinedQueryCarrier<A, B>
+where
+    A: EntityTrait + Send + 'static,
+    B: EntityTrait + Send + 'static,
+{
+    fn should_refresh(&self) -> bool;
+    fn set_refresh_interval(&mut self, interval: Option<Duration>);
+    fn pause_polling(&mut self);
+    fn resume_polling(&mut self);
+    fn query(&mut self, query: impl JoinedSelect<A, B>);
+    fn stored_query(&mut self, query: impl JoinedSelect<A, B>);
+    fn rerun_stored_query(&mut self);
+}
+
+impl<T, A, B> ImplJoinedQueryCarrier<A, B> for T
+where
+    T: HasJoinedQueryCarrier<A, B>,
+    A: EntityTrait + Send + 'static,
+    A::Model: Sync,
+    B: EntityTrait + Send + 'static,
+    B::Model: Sync,
+{
+    fn should_refresh(&self) -> bool {
+        self.ref_joined_query_carrier().should_refresh()
+    }
+    fn set_refresh_interval(&mut self, interval: Option<Duration>) {
+        self.ref_mut_joined_query_carrier()
+            .set_refresh_interval(interval);
+    }
+    fn pause_polling(&mut self) {
+        self.ref_mut_joined_query_carrier().pause_polling();
+    }
+    fn resume_polling(&mut self) {
+        self.ref_mut_joined_query_carrier().resume_polling();
+    }
+    fn query(&mut self, query: impl JoinedSelect<A, B>) {
+        self.ref_mut_joined_query_carrier().query(query);
+    }
+    fn stored_query(&mut self, query: impl JoinedSelect<A, B>) {
+        self.ref_mut_joined_query_carrier().stored_query(query);
+    }
+    fn rerun_stored_query(&mut self) {
+        self.ref_mut_joined_query_carrier().rerun_stored_query();
+    }
+}
+
+impl<A, B> HasQueryCarrier<Joined<A, B>> for JoinedQueryCarrier<A, B>
+where
+    A: EntityTrait + Send + 'static,
+    B: EntityTrait + Send + 'static,
+{
+    fn ref_query_carrier(&self) -> &QueryCarrier<Joined<A, B>> {
+        &self.carrier
+    }
+
+    fn ref_mut_query_carrier(&mut self) -> &mut QueryCarrier<Joined<A, B>> {
+        &mut self.carrier
+    }
+}
+
+pub(crate) trait HasJoinedQueryCarrier<A, B>
+where
+    A: EntityTrait + Send + 'static,
+    B: EntityTrait + Send + 'static,
+{
+    fn ref_joined_query_carrier(&self) -> &JoinedQueryCarrier<A, B>;
+    fn ref_mut_joined_query_carrier(&mut self) -> &mut JoinedQueryCarrier<A, B>;
+}
+
+/// Runs a select of two entities and reads every row as a [Joined] value,
+/// see [read_joined]
+async fn query_joined<A, B>(
+    db: &DatabaseConnection,
+    statement: Statement,
+) -> Result<Vec<Joined<A, B>>, DbErr>
+where
+    A: EntityTrait,
+    B: EntityTrait,
+{
+    read_joined::<A, B>(db.query_all(statement).await?)
+}
+
+/// Reads the rows of a [SelectTwo] or [SelectTwoMany] as [Joined] values.
+/// Rows of [SelectTwoMany] are not grouped, a row of `A` with several related
+/// rows of `B` appears once for each of them.
+fn read_joined<A, B>(rows: Vec<QueryResult>) -> Result<Vec<Joined<A, B>>, DbErr>
+where
+    A: EntityTrait,
+    B: EntityTrait,
+{
+    rows.into_iter()
+        .map(SelectTwoModel::<A::Model, B::Model>::from_raw_query_result)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{EntityTrait, ProxyRow, QueryResult, QueryTrait};
+
+    use super::read_joined;
+    use crate::{
+        carrier::query::PreparedQuery,
+        consts::DB_BACKEND,
+        test_db::{all_tables, prefixed_row, project, task},
+    };
+
+    /// Builds the row the database returns for a select of two entities,
+    /// `b_nulls` only gives the columns for a missing `b`
+    fn joined_row<A, B>(a: &A, b: Option<&B>, b_nulls: &B) -> QueryResult
+    where
+        A: sea_orm::ModelTrait,
+        B: sea_orm::ModelTrait,
+    {
+        let mut values = prefixed_row(a, "A_").values;
+        let b_values = match b {
+            Some(b) => prefixed_row(b, "B_").values,
+            None => prefixed_row(b_nulls, "B_")
+                .values
+                .into_iter()
+                .map(|(column, value)| (column, value.as_null()))
+                .collect(),
+        };
+        values.extend(b_values);
+        ProxyRow::new(values).into()
+    }
+
+    #[test]
+    fn select_two_rows_with_and_without_related() {
+        let select = task::Entity::find().find_also_related(project::Entity);
+        let prepared = PreparedQuery::new(select.clone(), &all_tables());
+        assert_eq!(prepared.tables, all_tables());
+        assert!(select.build(DB_BACKEND).to_string().contains("B_name"));
+
+        let rows = vec![
+            joined_row(
+                &task::model(1, Some(1)),
+                Some(&project::model(1)),
+                &project::model(0),
+            ),
+            joined_row(&task::model(2, None), None, &project::model(0)),
+        ];
+        let joined = read_joined::<task::Entity, project::Entity>(rows).unwrap();
+        assert_eq!(
+            joined,
+            vec![
+                (task::model(1, Some(1)), Some(project::model(1))),
+                (task::model(2, None), None)
+            ]
+        );
+    }
+
+    #[test]
+    fn select_two_many_yields_a_row_per_related() {
+        let select = project::Entity::find().find_with_related(task::Entity);
+        let prepared = PreparedQuery::new(select.clone(), &all_tables());
+        assert_eq!(prepared.tables, all_tables());
+
+        let no_task = task::model(0, None);
+        let rows = vec![
+            joined_row(&project::model(1), Some(&task::model(1, Some(1))), &no_task),
+            joined_row(&project::model(1), Some(&task::model(2, Some(1))), &no_task),
+            joined_row(&project::model(2), None, &no_task),
+        ];
+        let joined = read_joined::<project::Entity, task::Entity>(rows).unwrap();
+        assert_eq!(
+            joined,
+            vec![
+                (project::model(1), Some(task::model(1, Some(1)))),
+                (project::model(1), Some(task::model(2, Some(1)))),
+                (project::model(2), None),
+            ]
+        );
+
+        let mut grouped: Vec<(project::Model, Vec<task::Model>)> = vec![];
+        for (project, task) in joined {
+            match grouped.last_mut() {
+                Some((last, tasks)) if *last == project => tasks.extend(task),
+                _ => grouped.push((project, task.into_iter().collect())),
+            }
+        }
+        assert_eq!(
+            grouped,
+            vec![
+                (
+                    project::model(1),
+                    vec![task::model(1, Some(1)), task::model(2, Some(1))]
+                ),
+                (project::model(2), vec![]),
+            ]
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use std::future::Future;
 
+use chrono::Duration;
 use sea_orm::DatabaseConnection;
 use tokio::{
     sync::{mpsc, oneshot},
@@ -8,7 +9,10 @@ use tokio::{
 
 use crate::{container::builder::ContainerBuilder, messenger::ContainerData, TablesCollector};
 
-use super::query::{ExecutedQuery, HasQueryCarrier, ImplQueryCarrier, QueryCarrier};
+use super::{
+    query::{ExecutedQuery, HasQueryCarrier, ImplQueryCarrier, QueryCarrier},
+    shared_query::SharedQueries,
+};
 
 pub struct ManualQueryCarrier<Value>
 where
@@ -31,6 +35,7 @@ where
         all_tables: Vec<String>,
         tables_changed_sender: mpsc::Sender<Vec<String>>,
         new_register_sender: mpsc::Sender<ContainerData>,
+        shared_queries: SharedQueries,
     ) -> Self {
         Self::new(QueryCarrier::register_new(
             name,
@@ -38,19 +43,19 @@ where
             all_tables,
             tables_changed_sender,
             new_register_sender,
+            shared_queries,
         ))
     }
 
     pub fn manual_query<P, F>(&mut self, query_producer: P)
     where
+        Value: Sync,
         F: Future<Output = ExecutedQuery<Value>> + Send + 'static,
         P: FnOnce(DatabaseConnection, TablesCollector) -> F,
     {
         let (sender, reciever) = oneshot::channel();
         let collector = TablesCollector::new(self.carrier.all_tables.clone());
-        self.carrier
-            .should_update
-            .set_updating(collector.time_started);
+        self.carrier.start_updating(collector.time_started);
 
         let executing_query = query_producer(self.carrier.db.clone(), collector);
         task::spawn(async move {
@@ -84,9 +89,13 @@ where
     Value: Send + 'static,
 {
     fn should_refresh(&self) -> bool;
+    fn set_refresh_interval(&mut self, interval: Option<Duration>);
+    fn pause_polling(&mut self);
+    fn resume_polling(&mut self);
 
     fn manual_query<P, F>(&mut self, query_producer: P)
     where
+        Value: Sync,
         F: Future<Output = ExecutedQuery<Value>> + Send + 'static,
         P: FnOnce(DatabaseConnection, TablesCollector) -> F;
 }
@@ -98,6 +107,7 @@ where
 {
     fn manual_query<P, F>(&mut self, query_producer: P)
     where
+        Value: Sync,
         F: Future<Output = ExecutedQuery<Value>> + Send + 'static,
         P: FnOnce(DatabaseConnection, TablesCollector) -> F,
     {
@@ -108,6 +118,19 @@ where
     fn should_refresh(&self) -> bool {
         self.ref_manual_query_carrier().should_refresh()
     }
+
+    fn set_refresh_interval(&mut self, interval: Option<Duration>) {
+        self.ref_mut_manual_query_carrier()
+            .set_refresh_interval(interval);
+    }
+
+    fn pause_polling(&mut self) {
+        self.ref_mut_manual_query_carrier().pause_polling();
+    }
+
+    fn resume_polling(&mut self) {
+        self.ref_mut_manual_query_carrier().resume_polling();
+    }
 }
 
 pub(crate) trait HasManualQueryCarrier<Value>
@@ -1,8 +1,16 @@
-use std::{cmp::Ordering, mem};
+use std::{cmp::Ordering, future::Future, mem, sync::Arc};
 
-use crate::{container::builder::ContainerBuilder, messenger::ContainerData, TablesCollector};
-use chrono::{DateTime, FixedOffset};
-use sea_orm::{DatabaseConnection, DbErr};
+use crate::{
+    consts::DB_BACKEND,
+    container::{builder::ContainerBuilder, polling::Polling},
+    get_tables_present,
+    messenger::ContainerData,
+    TablesCollector,
+};
+
+use super::shared_query::{SharedQueries, SharedResult};
+use chrono::{DateTime, Duration, FixedOffset, Local};
+use sea_orm::{sea_query::SelectStatement, DatabaseConnection, DbErr, QueryTrait, Statement};
 use tokio::{
     sync::{
         mpsc,
@@ -10,7 +18,7 @@ use tokio::{
     },
     task,
 };
-use tracing::trace;
+use tracing::{info, trace};
 
 pub struct QueryCarrier<Value>
 where
@@ -23,13 +31,18 @@ where
 
     interesting_tables: Vec<String>,
     pub(super) executing_query: Option<oneshot::Receiver<ExecutedQuery<Value>>>,
+    pub(super) streaming_query: Option<StreamingQuery<Value>>,
+    pub(crate) stored_query: Option<PreparedQuery>,
     tables_interested_sender: mpsc::Sender<Vec<String>>,
 
     pub(super) should_update: UpdateState,
+    resolved_started: Option<DateTime<FixedOffset>>,
     time_of_change_reciver: mpsc::Receiver<DateTime<FixedOffset>>,
+    polling: Polling,
 
     new_register_sender: mpsc::Sender<ContainerData>,
     tables_changed_sender: mpsc::Sender<Vec<String>>,
+    pub(super) shared_queries: SharedQueries,
 }
 
 impl<Value> Clone for QueryCarrier<Value>
@@ -37,13 +50,16 @@ where
     Value: Send + 'static,
 {
     fn clone(&self) -> Self {
-        Self::register_new(
+        let mut carrier = Self::register_new(
             self.name.clone(),
             self.db.clone(),
             self.all_tables.clone(),
             self.tables_changed_sender.clone(),
             self.new_register_sender.clone(),
-        )
+            self.shared_queries.clone(),
+        );
+        carrier.stored_query = self.stored_query.clone();
+        carrier
     }
 }
 
@@ -57,6 +73,7 @@ where
         all_tables: Vec<String>,
         tables_changed_sender: mpsc::Sender<Vec<String>>,
         new_register_sender: mpsc::Sender<ContainerData>,
+        shared_queries: SharedQueries,
     ) -> Self {
         let (tables_interested_sender, tables_interested_reciever) = mpsc::channel(3);
 
@@ -75,9 +92,11 @@ where
             tables_changed_sender,
             update_reciver,
             new_register_sender,
+            shared_queries,
         )
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn new(
         name: String,
         pool: DatabaseConnection,
@@ -86,6 +105,7 @@ where
         tables_changed_sender: mpsc::Sender<Vec<String>>,
         update_reciver: mpsc::Receiver<DateTime<FixedOffset>>,
         new_register_sender: mpsc::Sender<ContainerData>,
+        shared_queries: SharedQueries,
     ) -> Self {
         Self {
             name,
@@ -93,11 +113,16 @@ where
             all_tables,
             interesting_tables: vec![],
             executing_query: None,
+            streaming_query: None,
+            stored_query: None,
             tables_interested_sender,
             tables_changed_sender,
             should_update: UpdateState::UpToDate,
+            resolved_started: None,
             time_of_change_reciver: update_reciver,
+            polling: Polling::default(),
             new_register_sender,
+            shared_queries,
         }
     }
 
@@ -109,24 +134,47 @@ where
         if let Ok(time_of_change) = self.time_of_change_reciver.try_recv() {
             self.should_update.set_should_update(time_of_change);
         }
+        if self.polling.is_due() {
+            self.polling.restart();
+            self.should_update.set_should_update(Local::now().into());
+        }
+    }
+
+    /// Marks the start of a new query, also restarting the polling interval
+    pub(super) fn start_updating(&mut self, time_started: DateTime<FixedOffset>) {
+        self.should_update.set_updating(time_started);
+        self.polling.restart();
+    }
+
+    /// Sets the interval in which the data should be refreshed even if no
+    /// change was detected, for example because another process writes to the
+    /// database. `None` disables polling.
+    pub fn set_refresh_interval(&mut self, interval: Option<Duration>) {
+        self.polling.set_interval(interval);
     }
 
-    pub fn try_resolve_query(&mut self) -> Option<Result<Vec<Value>, DbErr>> {
+    /// Stops polling until [QueryCarrier::resume_polling] is called, for
+    /// example while the data is not visible
+    pub fn pause_polling(&mut self) {
+        self.polling.pause();
+    }
+
+    pub fn resume_polling(&mut self) {
+        self.polling.resume();
+    }
+
+    pub fn try_resolve_query(&mut self) -> Option<SharedResult<Value>> {
         let mut executed_query = Option::take(&mut self.executing_query)?;
         match executed_query.try_recv() {
             Ok(result) => {
+                self.resolved_started = Some(result.time_started);
                 if let ExecutedQuery {
                     interested_tables,
                     query_result: Ok(_),
                     time_started,
                 } = result
                 {
-                    self.should_update.check_and_set_done(time_started);
-                    self.interesting_tables = interested_tables.clone();
-                    let sender = self.tables_interested_sender.clone();
-                    task::spawn(async move {
-                        let _ = sender.send(interested_tables).await;
-                    });
+                    self.set_done(interested_tables, time_started);
                 }
                 Some(result.query_result)
             }
@@ -139,24 +187,204 @@ where
         }
     }
 
+    /// Collects all rows a streaming query sent since the last call. The
+    /// first rows of a new result are returned as [StreamedRows::First] and
+    /// are meant to replace the previous values, all following ones as
+    /// [StreamedRows::More]. Further rows are held back until there are at
+    /// least as many as were returned so far, so that appending them costs
+    /// linear time over the whole stream.
+    pub fn try_resolve_stream(&mut self) -> Option<Result<StreamedRows<Value>, DbErr>> {
+        let mut streaming_query = self.streaming_query.take()?;
+        if let Some(error) = streaming_query.error.take() {
+            return Some(Err(error));
+        }
+        let finished = loop {
+            match streaming_query.reciver.try_recv() {
+                Ok(StreamMessage::Rows(batch)) => streaming_query.buffer.extend(batch),
+                Ok(StreamMessage::Done(result)) => break Some(result),
+                Err(mpsc::error::TryRecvError::Empty) => break None,
+                Err(mpsc::error::TryRecvError::Disconnected) => {
+                    break Some(Err(DbErr::Custom(String::from(
+                        "streaming query ended without a result",
+                    ))))
+                }
+            }
+        };
+
+        let buffered = streaming_query.buffer.len();
+        let done = matches!(finished, Some(Ok(())));
+        match finished {
+            Some(Err(error)) if buffered == 0 => return Some(Err(error)),
+            // The rows collected before the error are delivered first, the
+            // error with the next call
+            Some(Err(error)) => {
+                let _ = streaming_query.error.insert(error);
+            }
+            Some(Ok(())) => self.set_done(
+                streaming_query.interested_tables.clone(),
+                streaming_query.time_started,
+            ),
+            None if buffered == 0 || buffered < streaming_query.delivered => {
+                let _ = self.streaming_query.insert(streaming_query);
+                return None;
+            }
+            None => (),
+        }
+
+        let rows = mem::take(&mut streaming_query.buffer);
+        let first_rows = streaming_query.delivered == 0;
+        self.resolved_started = Some(streaming_query.time_started);
+        streaming_query.delivered += rows.len();
+        if !done {
+            let _ = self.streaming_query.insert(streaming_query);
+        }
+        match first_rows {
+            true => Some(Ok(StreamedRows::First(rows))),
+            false if rows.is_empty() => None,
+            false => Some(Ok(StreamedRows::More(rows))),
+        }
+    }
+
+    /// When the query of the last result returned by
+    /// [try_resolve_query](Self::try_resolve_query) or
+    /// [try_resolve_stream](Self::try_resolve_stream) was started
+    pub(crate) fn resolved_started(&self) -> Option<DateTime<FixedOffset>> {
+        self.resolved_started
+    }
+
+    /// Returns if a streaming query already delivered some but not yet all of
+    /// its rows
+    pub fn is_partial(&self) -> bool {
+        self.streaming_query
+            .as_ref()
+            .is_some_and(|streaming_query| streaming_query.delivered > 0)
+    }
+
+    fn set_done(&mut self, interested_tables: Vec<String>, time_started: DateTime<FixedOffset>) {
+        self.should_update.check_and_set_done(time_started);
+        self.interesting_tables = interested_tables.clone();
+        let sender = self.tables_interested_sender.clone();
+        task::spawn(async move {
+            let _ = sender.send(interested_tables).await;
+        });
+    }
+
     pub fn builder(&self) -> ContainerBuilder {
         ContainerBuilder::new(
             self.db.clone(),
             self.all_tables.clone(),
             self.tables_changed_sender.clone(),
             self.new_register_sender.clone(),
+            self.shared_queries.clone(),
         )
     }
 }
 
+/// Querying needs `Sync` values, since the rows of a query are shared with
+/// all containers running the same one, see [SharedQueries]
+impl<Value> QueryCarrier<Value>
+where
+    Value: Send + Sync + 'static,
+{
+    /// Does the query once with `run`, see [QueryCarrier::run]
+    pub(super) fn query<Query>(&mut self, query: Query, run: impl FnOnce(&mut Self, &PreparedQuery))
+    where
+        Query: QueryTrait<QueryStatement = SelectStatement>,
+    {
+        let prepared = PreparedQuery::new(query, &self.all_tables);
+        run(self, &prepared);
+    }
+
+    /// Does the query once with `run` and then stores it internally to redo
+    /// later
+    pub(super) fn stored_query<Query>(
+        &mut self,
+        query: Query,
+        run: impl FnOnce(&mut Self, &PreparedQuery),
+    ) where
+        Query: QueryTrait<QueryStatement = SelectStatement>,
+    {
+        let prepared = PreparedQuery::new(query, &self.all_tables);
+        run(self, &prepared);
+        let _ = self.stored_query.insert(prepared);
+    }
+
+    /// Reruns the stored query with its already built statement, does
+    /// nothing if no query was stored
+    pub(super) fn rerun_stored_query(&mut self, run: impl FnOnce(&mut Self, &PreparedQuery)) {
+        if let Some(prepared) = self.stored_query.take() {
+            run(self, &prepared);
+            let _ = self.stored_query.insert(prepared);
+        }
+    }
+
+    /// Runs a prepared query, where `fetch` gets the rows from the database.
+    /// Carriers running the same query share a single run of it.
+    pub(super) fn run<Fut>(
+        &mut self,
+        prepared: &PreparedQuery,
+        fetch: impl FnOnce(DatabaseConnection, Statement) -> Fut,
+    ) where
+        Fut: Future<Output = Result<Vec<Value>, DbErr>> + Send + 'static,
+    {
+        let time_started = Local::now().into();
+        self.start_updating(time_started);
+        prepared.log(&self.name);
+
+        let db = self.db.clone();
+        let statement = prepared.statement.clone();
+        let tables = prepared.tables.clone();
+        let shared = self
+            .shared_queries
+            .query(prepared.sql.clone(), tables.clone(), move || {
+                fetch(db, statement)
+            });
+        let (sender, reciever) = oneshot::channel();
+        task::spawn(async move {
+            let result = shared
+                .await
+                .unwrap_or_else(|_| Err(DbErr::Custom("shared query was dropped".into()).into()));
+            let _ = sender.send(ExecutedQuery::shared(tables, result, time_started));
+        });
+        self.streaming_query = None;
+        #[allow(unused_must_use)]
+        self.executing_query.insert(reciever);
+    }
+
+    /// Runs a prepared query whose rows arrive in batches from the reciever
+    /// `stream` returns, see [QueryCarrier::try_resolve_stream]
+    pub(super) fn run_streaming(
+        &mut self,
+        prepared: &PreparedQuery,
+        stream: impl FnOnce(DatabaseConnection, Statement) -> mpsc::Receiver<StreamMessage<Value>>,
+    ) {
+        let time_started = Local::now().into();
+        self.start_updating(time_started);
+        prepared.log(&self.name);
+
+        let reciever = stream(self.db.clone(), prepared.statement.clone());
+        self.executing_query = None;
+        let _ = self.streaming_query.insert(StreamingQuery::new(
+            reciever,
+            prepared.tables.clone(),
+            time_started,
+        ));
+    }
+}
+
 pub trait ImplQueryCarrier<Value>
 where
     Value: Send + 'static,
 {
     fn should_refresh(&self) -> bool;
     fn try_recive_should_update(&mut self);
-    fn try_resolve_query(&mut self) -> Option<Result<Vec<Value>, DbErr>>;
+    fn try_resolve_query(&mut self) -> Option<SharedResult<Value>>;
+    fn try_resolve_stream(&mut self) -> Option<Result<StreamedRows<Value>, DbErr>>;
+    fn is_partial(&self) -> bool;
     fn builder(&self) -> ContainerBuilder;
+    fn set_refresh_interval(&mut self, interval: Option<Duration>);
+    fn pause_polling(&mut self);
+    fn resume_polling(&mut self);
 }
 
 impl<T, Value> ImplQueryCarrier<Value> for T
@@ -172,13 +400,33 @@ where
         self.ref_mut_query_carrier().try_recive_should_update();
     }
 
-    fn try_resolve_query(&mut self) -> Option<Result<Vec<Value>, DbErr>> {
+    fn try_resolve_query(&mut self) -> Option<SharedResult<Value>> {
         self.ref_mut_query_carrier().try_resolve_query()
     }
 
+    fn try_resolve_stream(&mut self) -> Option<Result<StreamedRows<Value>, DbErr>> {
+        self.ref_mut_query_carrier().try_resolve_stream()
+    }
+
+    fn is_partial(&self) -> bool {
+        self.ref_query_carrier().is_partial()
+    }
+
     fn builder(&self) -> ContainerBuilder {
         self.ref_query_carrier().builder()
     }
+
+    fn set_refresh_interval(&mut self, interval: Option<Duration>) {
+        self.ref_mut_query_carrier().set_refresh_interval(interval);
+    }
+
+    fn pause_polling(&mut self) {
+        self.ref_mut_query_carrier().pause_polling();
+    }
+
+    fn resume_polling(&mut self) {
+        self.ref_mut_query_carrier().resume_polling();
+    }
 }
 
 pub(crate) trait HasQueryCarrier<Value>
@@ -194,7 +442,7 @@ where
     Value: Send + 'static,
 {
     interested_tables: Vec<String>,
-    query_result: Result<Vec<Value>, DbErr>,
+    query_result: SharedResult<Value>,
     time_started: DateTime<FixedOffset>,
 }
 
@@ -206,6 +454,17 @@ where
         interested_tables: Vec<String>,
         values: Result<Vec<Value>, DbErr>,
         time_started: DateTime<FixedOffset>,
+    ) -> Self {
+        let values = values.map(Arc::from).map_err(Arc::new);
+        Self::shared(interested_tables, values, time_started)
+    }
+
+    /// Same as [ExecutedQuery::new] but for rows or an error that might be
+    /// shared with other containers, see [SharedQueries]
+    pub(super) fn shared(
+        interested_tables: Vec<String>,
+        values: SharedResult<Value>,
+        time_started: DateTime<FixedOffset>,
     ) -> Self {
         Self {
             interested_tables,
@@ -217,12 +476,53 @@ where
     pub fn new_collector(collector: TablesCollector, values: Result<Vec<Value>, DbErr>) -> Self {
         Self {
             interested_tables: collector.tables.into_iter().collect(),
-            query_result: values,
+            query_result: values.map(Arc::from).map_err(Arc::new),
             time_started: collector.time_started,
         }
     }
 }
 
+/// Rows of a streaming query, see [QueryCarrier::try_resolve_stream]
+pub enum StreamedRows<Value> {
+    /// The first rows of a new result, replacing the previous values
+    First(Vec<Value>),
+    /// Further rows of the same result, to be appended
+    More(Vec<Value>),
+}
+
+pub(super) enum StreamMessage<Value> {
+    Rows(Vec<Value>),
+    Done(Result<(), DbErr>),
+}
+
+pub(super) struct StreamingQuery<Value> {
+    reciver: mpsc::Receiver<StreamMessage<Value>>,
+    interested_tables: Vec<String>,
+    time_started: DateTime<FixedOffset>,
+    /// Rows that arrived but were not returned yet
+    buffer: Vec<Value>,
+    /// Number of rows returned so far
+    delivered: usize,
+    error: Option<DbErr>,
+}
+
+impl<Value> StreamingQuery<Value> {
+    pub fn new(
+        reciver: mpsc::Receiver<StreamMessage<Value>>,
+        interested_tables: Vec<String>,
+        time_started: DateTime<FixedOffset>,
+    ) -> Self {
+        Self {
+            reciver,
+            interested_tables,
+            time_started,
+            buffer: vec![],
+            delivered: 0,
+            error: None,
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 pub(crate) enum UpdateState {
     ShouldUpdate,
@@ -360,3 +660,144 @@ impl UpdateState {
         }
     }
 }
+
+/// A select that was rendered once, so that it can be rerun as is without
+/// building the statement and searching its tables again
+#[derive(Clone)]
+pub(crate) struct PreparedQuery {
+    pub(super) statement: Statement,
+    pub(super) sql: String,
+    pub(super) tables: Vec<String>,
+}
+
+impl PreparedQuery {
+    pub(super) fn new<Query>(select: Query, all_tables: &[String]) -> Self
+    where
+        Query: QueryTrait<QueryStatement = SelectStatement>,
+    {
+        let statement = select.build(DB_BACKEND);
+        let sql = statement.to_string();
+        Self {
+            tables: get_tables_present(all_tables, &sql),
+            statement,
+            sql,
+        }
+    }
+
+    pub(super) fn log(&self, carrier_name: &str) {
+        const LIM: usize = 1000;
+        let sub_str = if self.sql.len() > LIM {
+            &self.sql[0..LIM]
+        } else {
+            &self.sql
+        };
+        info!("QueryCarrier: '{carrier_name}' has queried for {sub_str}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::Local;
+    use sea_orm::DbErr;
+    use tokio::sync::mpsc;
+
+    use super::{QueryCarrier, StreamMessage, StreamedRows, StreamingQuery};
+    use crate::{
+        carrier::shared_query::SharedQueries,
+        test_db::{self, all_tables, FakeDatabase},
+    };
+
+    fn carrier() -> QueryCarrier<u32> {
+        let (tables_changed_sender, _) = mpsc::channel(10);
+        let (new_register_sender, _) = mpsc::channel(10);
+        QueryCarrier::register_new(
+            "test".to_string(),
+            test_db::connect(&Arc::new(FakeDatabase::default())),
+            all_tables(),
+            tables_changed_sender,
+            new_register_sender,
+            SharedQueries::default(),
+        )
+    }
+
+    fn stream(carrier: &mut QueryCarrier<u32>) -> mpsc::Sender<StreamMessage<u32>> {
+        let (sender, reciver) = mpsc::channel(10);
+        let streaming_query = StreamingQuery::new(reciver, all_tables(), Local::now().into());
+        let _ = carrier.streaming_query.insert(streaming_query);
+        sender
+    }
+
+    #[tokio::test]
+    async fn empty_stream_replaces_the_values() {
+        let mut carrier = carrier();
+        let sender = stream(&mut carrier);
+        assert!(carrier.try_resolve_stream().is_none());
+
+        sender.send(StreamMessage::Done(Ok(()))).await.unwrap();
+        assert!(
+            matches!(carrier.try_resolve_stream(), Some(Ok(StreamedRows::First(rows))) if rows.is_empty())
+        );
+        assert!(!carrier.is_partial());
+        assert!(carrier.try_resolve_stream().is_none());
+    }
+
+    #[tokio::test]
+    async fn rows_are_delivered_before_the_error() {
+        let mut carrier = carrier();
+        let sender = stream(&mut carrier);
+        sender.send(StreamMessage::Rows(vec![1, 2])).await.unwrap();
+        let error = DbErr::Custom("connection lost".to_string());
+        sender.send(StreamMessage::Done(Err(error))).await.unwrap();
+
+        assert!(
+            matches!(carrier.try_resolve_stream(), Some(Ok(StreamedRows::First(rows))) if rows == [1, 2])
+        );
+        assert!(matches!(
+            carrier.try_resolve_stream(),
+            Some(Err(DbErr::Custom(_)))
+        ));
+        assert!(carrier.try_resolve_stream().is_none());
+    }
+
+    #[tokio::test]
+    async fn further_rows_are_held_back_until_they_double_the_data() {
+        let mut carrier = carrier();
+        let sender = stream(&mut carrier);
+        sender.send(StreamMessage::Rows(vec![1, 2])).await.unwrap();
+        assert!(matches!(
+            carrier.try_resolve_stream(),
+            Some(Ok(StreamedRows::First(_)))
+        ));
+        assert!(carrier.is_partial());
+
+        sender.send(StreamMessage::Rows(vec![3])).await.unwrap();
+        assert!(carrier.try_resolve_stream().is_none());
+        sender.send(StreamMessage::Rows(vec![4])).await.unwrap();
+        assert!(
+            matches!(carrier.try_resolve_stream(), Some(Ok(StreamedRows::More(rows))) if rows == [3, 4])
+        );
+
+        sender.send(StreamMessage::Rows(vec![5])).await.unwrap();
+        sender.send(StreamMessage::Done(Ok(()))).await.unwrap();
+        assert!(
+            matches!(carrier.try_resolve_stream(), Some(Ok(StreamedRows::More(rows))) if rows == [5])
+        );
+        assert!(!carrier.is_partial());
+    }
+
+    #[tokio::test]
+    async fn superseded_stream_is_dropped() {
+        let mut carrier = carrier();
+        let superseded = stream(&mut carrier);
+        superseded.send(StreamMessage::Rows(vec![1])).await.unwrap();
+        let sender = stream(&mut carrier);
+
+        assert!(superseded.send(StreamMessage::Rows(vec![2])).await.is_err());
+        sender.send(StreamMessage::Rows(vec![3])).await.unwrap();
+        assert!(
+            matches!(carrier.try_resolve_stream(), Some(Ok(StreamedRows::First(rows))) if rows == [3])
+        );
+    }
+}
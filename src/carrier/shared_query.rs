@@ -0,0 +1,409 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    future::Future,
+    mem,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Duration, FixedOffset, Local};
+use sea_orm::DbErr;
+use tokio::{sync::oneshot, task};
+
+type QueryKey = (TypeId, String);
+type Subscribers<Model> = Vec<oneshot::Sender<SharedResult<Model>>>;
+
+/// The result of a shared query, handed out to every container that asked
+/// for it without copying the rows or losing the original error
+pub(crate) type SharedResult<Model> = Result<Arc<[Model]>, Arc<DbErr>>;
+
+/// Queries shared by all containers of one [Messenger](crate::messenger::Messenger).
+///
+/// Identical queries, meaning the same rendered SQL including its values and
+/// the same resulting model, that are started while another one is still
+/// running are not sent to the database again. Instead they wait for the
+/// running query and all receive its result. Queries started before the
+/// last [invalidation](SharedQueries::invalidate) are never joined, since
+/// they might not see the change that caused it.
+///
+/// If the [QueryCache] is enabled, finished results are also kept around and
+/// handed out to identical queries until one of their tables changes.
+#[derive(Clone, Default)]
+pub struct SharedQueries {
+    in_flight: Arc<Mutex<InFlight>>,
+    cache: Arc<Mutex<Option<QueryCache>>>,
+}
+
+/// Running queries keyed by the invalidation generation they were started in
+#[derive(Default)]
+struct InFlight {
+    generation: u64,
+    queries: HashMap<(QueryKey, u64), Box<dyn Any + Send>>,
+}
+
+impl SharedQueries {
+    /// Runs the query created by `run` unless an identical one is cached or
+    /// already in flight since the last invalidation, in which case the
+    /// returned reciver resolves with that result. `tables` are the tables
+    /// the query reads from.
+    pub(crate) fn query<Model, Fut>(
+        &self,
+        sql: String,
+        tables: Vec<String>,
+        run: impl FnOnce() -> Fut,
+    ) -> oneshot::Receiver<SharedResult<Model>>
+    where
+        Model: Send + Sync + 'static,
+        Fut: Future<Output = Result<Vec<Model>, DbErr>> + Send + 'static,
+    {
+        let key = (TypeId::of::<Model>(), sql);
+        let (sender, reciver) = oneshot::channel();
+
+        let cache_as = match self.cache.lock().unwrap().as_mut() {
+            Some(cache) => {
+                if let Some(rows) = cache.get::<Model>(&key) {
+                    let _ = sender.send(Ok(rows));
+                    return reciver;
+                }
+                Some((cache.generation, tables))
+            }
+            None => None,
+        };
+        self.query_in_flight(key, sender, reciver, run, cache_as)
+    }
+
+    fn query_in_flight<Model, Fut>(
+        &self,
+        key: QueryKey,
+        sender: oneshot::Sender<SharedResult<Model>>,
+        reciver: oneshot::Receiver<SharedResult<Model>>,
+        run: impl FnOnce() -> Fut,
+        cache_as: Option<(u64, Vec<String>)>,
+    ) -> oneshot::Receiver<SharedResult<Model>>
+    where
+        Model: Send + Sync + 'static,
+        Fut: Future<Output = Result<Vec<Model>, DbErr>> + Send + 'static,
+    {
+        let in_flight_key = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            let in_flight_key = (key.clone(), in_flight.generation);
+            if let Some(subscribers) = in_flight
+                .queries
+                .get_mut(&in_flight_key)
+                .and_then(|subscribers| subscribers.downcast_mut::<Subscribers<Model>>())
+            {
+                subscribers.push(sender);
+                return reciver;
+            }
+            in_flight.queries.insert(
+                in_flight_key.clone(),
+                Box::new(vec![sender] as Subscribers<Model>),
+            );
+            in_flight_key
+        };
+
+        let in_flight = self.in_flight.clone();
+        let cache = self.cache.clone();
+        let query = run();
+        task::spawn(async move {
+            let result: SharedResult<Model> = query.await.map(Arc::from).map_err(Arc::new);
+            if let (Ok(rows), Some((generation, tables))) = (&result, cache_as) {
+                if let Some(cache) = cache.lock().unwrap().as_mut() {
+                    cache.insert(key, rows.clone(), tables, generation);
+                }
+            }
+            let subscribers = in_flight
+                .lock()
+                .unwrap()
+                .queries
+                .remove(&in_flight_key)
+                .and_then(|subscribers| subscribers.downcast::<Subscribers<Model>>().ok())
+                .map(|subscribers| *subscribers)
+                .unwrap_or_default();
+            for subscriber in subscribers {
+                let _ = subscriber.send(result.clone());
+            }
+        });
+        reciver
+    }
+
+    /// Enables the result cache or replaces its settings, which clears it
+    pub(crate) fn enable_cache(&self, ttl: Duration, memory_budget: usize) {
+        let _ = self
+            .cache
+            .lock()
+            .unwrap()
+            .insert(QueryCache::new(ttl, memory_budget));
+    }
+
+    pub(crate) fn disable_cache(&self) {
+        self.cache.lock().unwrap().take();
+    }
+
+    /// Evicts all cached results that read from one of the `tables`. Queries
+    /// still running are not joined by later ones anymore.
+    pub(crate) fn invalidate(&self, tables: &[String]) {
+        self.in_flight.lock().unwrap().generation += 1;
+        if let Some(cache) = self.cache.lock().unwrap().as_mut() {
+            cache.invalidate(tables);
+        }
+    }
+}
+
+/// Results of recent queries, evicted when one of their tables changes,
+/// after the `ttl` or when the `memory_budget` in bytes would be exceeded.
+///
+/// The memory of an entry is estimated as `size_of::<Model>()` times its
+/// rows. Heap allocations the rows point to, like strings, are not counted,
+/// so the actual memory used can be considerably larger.
+struct QueryCache {
+    ttl: Duration,
+    memory_budget: usize,
+    used_memory: usize,
+    /// Incremented on every invalidation, results of queries started in an
+    /// older generation might already be outdated and are not cached
+    generation: u64,
+    entries: HashMap<QueryKey, CacheEntry>,
+}
+
+struct CacheEntry {
+    rows: Box<dyn Any + Send>,
+    tables: Vec<String>,
+    size: usize,
+    stored_at: DateTime<FixedOffset>,
+}
+
+impl QueryCache {
+    fn new(ttl: Duration, memory_budget: usize) -> Self {
+        Self {
+            ttl,
+            memory_budget,
+            used_memory: 0,
+            generation: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn get<Model>(&mut self, key: &QueryKey) -> Option<Arc<[Model]>>
+    where
+        Model: Send + Sync + 'static,
+    {
+        let entry = self.entries.get(key)?;
+        if Local::now().fixed_offset() - entry.stored_at >= self.ttl {
+            self.remove(key);
+            return None;
+        }
+        entry.rows.downcast_ref::<Arc<[Model]>>().cloned()
+    }
+
+    fn insert<Model>(
+        &mut self,
+        key: QueryKey,
+        rows: Arc<[Model]>,
+        tables: Vec<String>,
+        generation: u64,
+    ) where
+        Model: Send + Sync + 'static,
+    {
+        let size = mem::size_of::<Model>() * rows.len();
+        if generation != self.generation || size > self.memory_budget {
+            return;
+        }
+        self.remove(&key);
+        self.evict_expired();
+        while self.used_memory + size > self.memory_budget {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.stored_at)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+
+        self.used_memory += size;
+        self.entries.insert(
+            key,
+            CacheEntry {
+                rows: Box::new(rows),
+                tables,
+                size,
+                stored_at: Local::now().into(),
+            },
+        );
+    }
+
+    fn invalidate(&mut self, tables: &[String]) {
+        self.generation += 1;
+        let outdated = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.tables.iter().any(|table| tables.contains(table)))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        outdated.iter().for_each(|key| self.remove(key));
+    }
+
+    fn evict_expired(&mut self) {
+        let now = Local::now().fixed_offset();
+        let expired = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| now - entry.stored_at >= self.ttl)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        expired.iter().for_each(|key| self.remove(key));
+    }
+
+    fn remove(&mut self, key: &QueryKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.used_memory -= entry.size;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn identical_queries_run_once() {
+        let shared = SharedQueries::default();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let (start_sender, start_reciver) = oneshot::channel::<()>();
+
+        let run = |runs: Arc<AtomicUsize>, start: Option<oneshot::Receiver<()>>| {
+            move || async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+                if let Some(start) = start {
+                    let _ = start.await;
+                }
+                Ok(vec![1, 2, 3])
+            }
+        };
+
+        let first = shared.query(
+            "SELECT 1".into(),
+            vec![],
+            run(runs.clone(), Some(start_reciver)),
+        );
+        let second = shared.query("SELECT 1".into(), vec![], run(runs.clone(), None));
+        let other = shared.query::<i32, _>("SELECT 2".into(), vec![], run(runs.clone(), None));
+        let _ = start_sender.send(());
+
+        assert_eq!(*first.await.unwrap().unwrap(), [1, 2, 3]);
+        assert_eq!(*second.await.unwrap().unwrap(), [1, 2, 3]);
+        assert_eq!(*other.await.unwrap().unwrap(), [1, 2, 3]);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn subscribers_share_rows_and_error() {
+        let shared = SharedQueries::default();
+        let (start_sender, start_reciver) = oneshot::channel::<()>();
+        let first = shared.query("SELECT 1".into(), vec![], move || async move {
+            let _ = start_reciver.await;
+            Ok(vec![1, 2, 3])
+        });
+        let second = shared.query("SELECT 1".into(), vec![], || async { Ok(vec![]) });
+        let _ = start_sender.send(());
+        let (first, second) = (
+            first.await.unwrap().unwrap(),
+            second.await.unwrap().unwrap(),
+        );
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let (start_sender, start_reciver) = oneshot::channel::<()>();
+        let first = shared.query::<i32, _>("SELECT 2".into(), vec![], move || async move {
+            let _ = start_reciver.await;
+            Err(DbErr::RecordNotFound("row".into()))
+        });
+        let second = shared.query::<i32, _>("SELECT 2".into(), vec![], || async { Ok(vec![]) });
+        let _ = start_sender.send(());
+        for reciver in [first, second] {
+            let error = reciver.await.unwrap().unwrap_err();
+            assert!(matches!(*error, DbErr::RecordNotFound(_)));
+        }
+    }
+
+    #[tokio::test]
+    async fn queries_after_invalidation_are_not_joined() {
+        let shared = SharedQueries::default();
+        let (start_sender, start_reciver) = oneshot::channel::<()>();
+        let before = shared.query("SELECT 1".into(), vec![], move || async move {
+            let _ = start_reciver.await;
+            Ok(vec![1])
+        });
+        shared.invalidate(&["\"a\"".to_string()]);
+        let after = shared.query("SELECT 1".into(), vec![], || async { Ok(vec![2]) });
+
+        assert_eq!(*after.await.unwrap().unwrap(), [2]);
+        let _ = start_sender.send(());
+        assert_eq!(*before.await.unwrap().unwrap(), [1]);
+    }
+
+    #[tokio::test]
+    async fn cached_results_until_invalidated() {
+        let shared = SharedQueries::default();
+        shared.enable_cache(Duration::minutes(1), 1024);
+        let runs = Arc::new(AtomicUsize::new(0));
+        let run = |runs: Arc<AtomicUsize>| {
+            move || async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+                Ok(vec![1, 2, 3])
+            }
+        };
+        let tables = vec!["\"a\"".to_string()];
+
+        let first = shared.query("SELECT 1".into(), tables.clone(), run(runs.clone()));
+        assert_eq!(*first.await.unwrap().unwrap(), [1, 2, 3]);
+        let cached = shared.query("SELECT 1".into(), tables.clone(), run(runs.clone()));
+        assert_eq!(*cached.await.unwrap().unwrap(), [1, 2, 3]);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        shared.invalidate(&["\"b\"".to_string()]);
+        let cached = shared.query("SELECT 1".into(), tables.clone(), run(runs.clone()));
+        assert_eq!(*cached.await.unwrap().unwrap(), [1, 2, 3]);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        shared.invalidate(&tables);
+        let requeried = shared.query("SELECT 1".into(), tables, run(runs.clone()));
+        assert_eq!(*requeried.await.unwrap().unwrap(), [1, 2, 3]);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn outdated_results_are_not_cached() {
+        let shared = SharedQueries::default();
+        shared.enable_cache(Duration::minutes(1), 1024);
+        let tables = vec!["\"a\"".to_string()];
+        let (start_sender, start_reciver) = oneshot::channel::<()>();
+        let before = shared.query("SELECT 1".into(), tables.clone(), move || async move {
+            let _ = start_reciver.await;
+            Ok(vec![1])
+        });
+        shared.invalidate(&tables);
+        let _ = start_sender.send(());
+        assert_eq!(*before.await.unwrap().unwrap(), [1]);
+
+        let after = shared.query("SELECT 1".into(), tables, || async { Ok(vec![2]) });
+        assert_eq!(*after.await.unwrap().unwrap(), [2]);
+    }
+
+    #[test]
+    fn cache_stays_within_budget() {
+        let mut cache = QueryCache::new(Duration::minutes(1), 64);
+        let key = |sql: &str| (TypeId::of::<u64>(), sql.to_string());
+        cache.insert(key("first"), Arc::new([0u64; 4]), vec![], 0);
+        cache.insert(key("second"), Arc::new([0u64; 4]), vec![], 0);
+        cache.insert(key("third"), Arc::new([0u64; 4]), vec![], 0);
+
+        assert!(cache.used_memory <= cache.memory_budget);
+        assert_eq!(cache.entries.len(), 2);
+        assert!(cache.get::<u64>(&key("third")).is_some());
+    }
+}
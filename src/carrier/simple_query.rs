@@ -1,16 +1,20 @@
-use std::{future::Future, pin::Pin};
-
-use chrono::Local;
-use sea_orm::{DatabaseConnection, DbErr, EntityTrait, QueryTrait, Select};
-use tokio::{
-    sync::{mpsc, oneshot},
-    task,
+use std::{
+    future::Future,
+    mem,
+    pin::{pin, Pin},
 };
-use tracing::info;
 
-use crate::{get_tables_present, messenger::ContainerData, QUERY_BUILDER};
+use chrono::Duration;
+use futures_util::TryStreamExt;
+use sea_orm::{DatabaseConnection, DbErr, EntityTrait, Select, Statement};
+use tokio::{sync::mpsc, task};
+
+use crate::messenger::ContainerData;
 
-use super::query::{ExecutedQuery, HasQueryCarrier, ImplQueryCarrier, QueryCarrier};
+use super::{
+    query::{HasQueryCarrier, ImplQueryCarrier, PreparedQuery, QueryCarrier, StreamMessage},
+    shared_query::SharedQueries,
+};
 
 #[derive(Clone)]
 pub struct SimpleQueryCarrier<DbValue>
@@ -18,21 +22,26 @@ where
     DbValue: EntityTrait + Send + 'static,
 {
     carrier: QueryCarrier<DbValue::Model>,
-
-    pub(crate) stored_select: Option<Select<DbValue>>,
+    streaming: bool,
 }
 
+/// Amount of rows a streaming query collects before sending them
+const STREAM_BATCH_SIZE: usize = 250;
+const STREAM_CHANNEL_SIZE: usize = 16;
+
 impl<DbValue> SimpleQueryCarrier<DbValue>
 where
     DbValue: EntityTrait + Send + 'static,
 {
     pub fn new(
-        carrier: QueryCarrier<DbValue::Model>,
+        mut carrier: QueryCarrier<DbValue::Model>,
         stored_select: Option<Select<DbValue>>,
     ) -> Self {
+        carrier.stored_query =
+            stored_select.map(|select| PreparedQuery::new(select, &carrier.all_tables));
         Self {
             carrier,
-            stored_select,
+            streaming: false,
         }
     }
 
@@ -42,6 +51,7 @@ where
         all_tables: Vec<String>,
         tables_changed_sender: mpsc::Sender<Vec<String>>,
         new_register_sender: mpsc::Sender<ContainerData>,
+        shared_queries: SharedQueries,
     ) -> Self {
         let carrier = QueryCarrier::register_new(
             name,
@@ -49,44 +59,76 @@ where
             all_tables,
             tables_changed_sender,
             new_register_sender,
+            shared_queries,
         );
         Self::new(carrier, None)
     }
+}
 
-    pub fn query(&mut self, mut query: Select<DbValue>) {
-        let time_started = Local::now().into();
-        self.carrier.should_update.set_updating(time_started);
+impl<DbValue> SimpleQueryCarrier<DbValue>
+where
+    DbValue: EntityTrait + Send + 'static,
+    DbValue::Model: Sync,
+{
+    pub fn query(&mut self, query: Select<DbValue>) {
+        self.carrier.query(query, Self::run(self.streaming));
+    }
 
-        let db = self.carrier.db.clone();
-        let (sender, reciever) = oneshot::channel();
-        let query_string = query.query().to_string(QUERY_BUILDER);
-        let tables = get_tables_present(&self.carrier.all_tables, &query_string);
-
-        {
-            const LIM: usize = 1000;
-            let sub_str = if query_string.len() > LIM {
-                &query_string[0..LIM]
-            } else {
-                &query_string
-            };
-            info!(
-                "QueryCarrier: '{}' has queried for {}",
-                self.carrier.name, sub_str
-            );
+    fn run(streaming: bool) -> impl FnOnce(&mut QueryCarrier<DbValue::Model>, &PreparedQuery) {
+        move |carrier, prepared| match streaming {
+            true => carrier.run_streaming(prepared, Self::stream),
+            false => carrier.run(prepared, |db, statement| async move {
+                DbValue::find().from_raw_sql(statement).all(&db).await
+            }),
         }
+    }
 
+    fn stream(
+        db: DatabaseConnection,
+        statement: Statement,
+    ) -> mpsc::Receiver<StreamMessage<DbValue::Model>> {
+        let (sender, reciever) = mpsc::channel(STREAM_CHANNEL_SIZE);
         task::spawn(async move {
-            let result = query.into_model::<DbValue::Model>().all(&db).await;
-            let _ = sender.send(ExecutedQuery::new(tables, result, time_started));
+            let result = async {
+                let mut stream = pin!(DbValue::find().from_raw_sql(statement).stream(&db).await?);
+                let mut batch = Vec::with_capacity(STREAM_BATCH_SIZE);
+                while let Some(row) = stream.try_next().await? {
+                    batch.push(row);
+                    if batch.len() >= STREAM_BATCH_SIZE {
+                        let rows = mem::replace(&mut batch, Vec::with_capacity(STREAM_BATCH_SIZE));
+                        if sender.send(StreamMessage::Rows(rows)).await.is_err() {
+                            // the query was superseded, nobody is waiting for the rows anymore
+                            return Ok(());
+                        }
+                    }
+                }
+                if !batch.is_empty() {
+                    let _ = sender.send(StreamMessage::Rows(batch)).await;
+                }
+                Ok(())
+            }
+            .await;
+            let _ = sender.send(StreamMessage::Done(result)).await;
         });
-        #[allow(unused_must_use)]
-        self.carrier.executing_query.insert(reciever);
+        reciever
+    }
+
+    /// When enabled, queries deliver their rows in batches while they arrive
+    /// instead of all at once after the query finished. Useful for long
+    /// running queries, so that the first rows can already be shown.
+    pub fn set_streaming(&mut self, streaming: bool) {
+        self.streaming = streaming;
     }
 
     /// Does the action once and then stores it internally to redo later
     pub fn stored_query(&mut self, query: Select<DbValue>) {
-        self.query(query.clone());
-        let _ = self.stored_select.insert(query);
+        self.carrier.stored_query(query, Self::run(self.streaming));
+    }
+
+    /// Reruns the stored query with its already built statement, does
+    /// nothing if no query was stored
+    pub fn rerun_stored_query(&mut self) {
+        self.carrier.rerun_stored_query(Self::run(self.streaming));
     }
 
     pub fn direct_query<OneTtimeValue>(
@@ -106,8 +148,14 @@ where
     DbValue: EntityTrait + Send + 'static,
 {
     fn should_refresh(&self) -> bool;
+    fn set_refresh_interval(&mut self, interval: Option<Duration>);
+    fn pause_polling(&mut self);
+    fn resume_polling(&mut self);
+    fn set_streaming(&mut self, streaming: bool);
+    fn is_partial(&self) -> bool;
     fn query(&mut self, query: Select<DbValue>);
     fn stored_query(&mut self, query: Select<DbValue>);
+    fn rerun_stored_query(&mut self);
     fn direct_query<OneTtimeValue>(
         &self,
         query: Select<OneTtimeValue>,
@@ -120,16 +168,36 @@ impl<T, DbValue> ImplSimpleQueryCarrier<DbValue> for T
 where
     T: HasSimpleQueryCarrier<DbValue>,
     DbValue: EntityTrait + Send + 'static,
+    DbValue::Model: Sync,
 {
     fn should_refresh(&self) -> bool {
         self.ref_simple_query_carrier().should_refresh()
     }
+    fn set_refresh_interval(&mut self, interval: Option<Duration>) {
+        self.ref_mut_simple_query_carrier()
+            .set_refresh_interval(interval);
+    }
+    fn pause_polling(&mut self) {
+        self.ref_mut_simple_query_carrier().pause_polling();
+    }
+    fn resume_polling(&mut self) {
+        self.ref_mut_simple_query_carrier().resume_polling();
+    }
+    fn set_streaming(&mut self, streaming: bool) {
+        self.ref_mut_simple_query_carrier().set_streaming(streaming);
+    }
+    fn is_partial(&self) -> bool {
+        self.ref_simple_query_carrier().is_partial()
+    }
     fn query(&mut self, query: Select<DbValue>) {
         self.ref_mut_simple_query_carrier().query(query);
     }
     fn stored_query(&mut self, query: Select<DbValue>) {
         self.ref_mut_simple_query_carrier().stored_query(query);
     }
+    fn rerun_stored_query(&mut self) {
+        self.ref_mut_simple_query_carrier().rerun_stored_query();
+    }
     fn direct_query<OneTtimeValue>(
         &self,
         query: Select<OneTtimeValue>,
@@ -164,3 +232,82 @@ where
 
 pub type DirectQueryFuture<Type> =
     Pin<Box<dyn Future<Output = Result<Vec<Type>, DbErr>> + Send + 'static>>;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use sea_orm::{EntityTrait, Statement};
+    use tokio::{sync::mpsc, task::yield_now};
+
+    use super::SimpleQueryCarrier;
+    use crate::{
+        carrier::shared_query::SharedQueries,
+        consts::DB_BACKEND,
+        test_db::{self, all_tables, row, task, FakeDatabase},
+    };
+
+    fn carrier(
+        fake: &Arc<FakeDatabase>,
+        shared_queries: SharedQueries,
+    ) -> SimpleQueryCarrier<task::Entity> {
+        let (tables_changed_sender, _) = mpsc::channel(10);
+        let (new_register_sender, _) = mpsc::channel(10);
+        SimpleQueryCarrier::register_new(
+            "test".to_string(),
+            test_db::connect(fake),
+            all_tables(),
+            tables_changed_sender,
+            new_register_sender,
+            shared_queries,
+        )
+    }
+
+    #[tokio::test]
+    async fn identical_queries_share_their_rows() {
+        let fake = Arc::new(FakeDatabase::default());
+        fake.push_query(Ok(vec![row(&task::model(1, None))]));
+        let shared_queries = SharedQueries::default();
+        let mut carriers = [(); 2].map(|_| carrier(&fake, shared_queries.clone()));
+        for carrier in carriers.iter_mut() {
+            carrier.query(task::Entity::find());
+        }
+
+        let mut results = vec![];
+        while results.len() < 2 {
+            yield_now().await;
+            for carrier in carriers.iter_mut() {
+                results.extend(carrier.carrier.try_resolve_query());
+            }
+        }
+        let [first, second] = &results[..] else {
+            unreachable!()
+        };
+        let (first, second) = (first.as_ref().unwrap(), second.as_ref().unwrap());
+        assert_eq!(first[..], [task::model(1, None)]);
+        assert!(Arc::ptr_eq(first, second));
+    }
+
+    #[tokio::test]
+    async fn rerun_runs_the_stored_statement() {
+        let fake = Arc::new(FakeDatabase::default());
+        let mut carrier = carrier(&fake, SharedQueries::default());
+        carrier.stored_query(task::Entity::find());
+        while carrier.carrier.try_resolve_query().is_none() {
+            yield_now().await;
+        }
+
+        // a statement that can't come from rendering the select again
+        let stored = carrier.carrier.stored_query.as_mut().unwrap();
+        stored.statement = Statement::from_string(DB_BACKEND, "SELECT 'stored'");
+        stored.sql = stored.statement.to_string();
+        carrier.rerun_stored_query();
+        while carrier.carrier.try_resolve_query().is_none() {
+            yield_now().await;
+        }
+
+        let statements = fake.statements();
+        assert_eq!(statements.len(), 2);
+        assert_eq!(statements[1], "SELECT 'stored'");
+    }
+}
@@ -3,6 +3,7 @@ use sea_orm::DbBackend;
 use sea_query::MysqlQueryBuilder;
 #[cfg(feature = "psql")]
 use sea_query::PostgresQueryBuilder;
+use sea_query::QuotedBuilder;
 #[cfg(feature = "sqlite")]
 use sea_query::SqliteQueryBuilder;
 
@@ -44,3 +45,10 @@ pub(crate) const QUERY_BUILDER: SqliteQueryBuilder = SqliteQueryBuilder;
 ))]
 pub(crate) const QUERY_BUILDER: SqliteQueryBuilder =
     panic!("you have to activate one of the features");
+
+/// Quotes `table` the way [QUERY_BUILDER] quotes it in queries, which is how
+/// tables are found in queries and reported as changed
+pub(crate) fn quote_table(table: &str) -> String {
+    let quote = QUERY_BUILDER.quote();
+    format!("{}{table}{}", quote.left(), quote.right())
+}
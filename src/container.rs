@@ -1,14 +1,24 @@
 pub mod data;
+pub mod derived;
+pub(crate) mod polling;
 pub mod tasked;
 
 #[cfg(any(feature = "psql", feature = "mysql", feature = "sqlite"))]
 pub mod builder;
 #[cfg(any(feature = "psql", feature = "mysql", feature = "sqlite"))]
+pub(crate) mod invalidation;
+#[cfg(any(feature = "psql", feature = "mysql", feature = "sqlite"))]
+pub mod joined;
+#[cfg(any(feature = "psql", feature = "mysql", feature = "sqlite"))]
 pub mod manual;
 #[cfg(any(feature = "psql", feature = "mysql", feature = "sqlite"))]
+pub mod nested;
+#[cfg(any(feature = "psql", feature = "mysql", feature = "sqlite"))]
 pub mod projecting;
 #[cfg(any(feature = "psql", feature = "mysql", feature = "sqlite"))]
 pub mod simple;
+#[cfg(any(feature = "psql", feature = "mysql", feature = "sqlite"))]
+pub mod tree;
 
 pub fn create_name<C, T>() -> String {
     format!("{}<{}>", type_name::<C>(2), type_name::<T>(1))
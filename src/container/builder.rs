@@ -1,9 +1,17 @@
+use std::{future::Future, hash::Hash, sync::Arc};
+
 use sea_orm::{DatabaseConnection, EntityTrait};
 use tokio::sync::mpsc;
 
 use crate::{
-    carrier::{self, execute::ExecuteCarrier, simple_query::SimpleQueryCarrier},
-    container::{manual, projecting::ProjectingContainer, simple},
+    carrier::{
+        self, execute::ExecuteCarrier, shared_query::SharedQueries,
+        simple_query::SimpleQueryCarrier,
+    },
+    container::{
+        invalidation::Invalidation, joined::JoinedContainer, manual, nested::NestedContainer,
+        projecting::ProjectingContainer, simple, tasked, tree::TreeContainer,
+    },
     messenger::ContainerData,
     FromEntity, ToEntity,
 };
@@ -13,6 +21,7 @@ pub struct ContainerBuilder {
     all_tables: Vec<String>,
     tables_changed_sender: mpsc::Sender<Vec<String>>,
     new_register_sender: mpsc::Sender<ContainerData>,
+    shared_queries: SharedQueries,
     file: Option<String>,
 }
 
@@ -22,12 +31,14 @@ impl ContainerBuilder {
         all_tables: Vec<String>,
         tables_changed_sender: mpsc::Sender<Vec<String>>,
         new_register_sender: mpsc::Sender<ContainerData>,
+        shared_queries: SharedQueries,
     ) -> Self {
         Self {
             pool,
             all_tables,
             tables_changed_sender,
             new_register_sender,
+            shared_queries,
             file: None,
         }
     }
@@ -59,12 +70,33 @@ impl ContainerBuilder {
     where
         Value: Clone + Send + 'static,
         DbValue: EntityTrait + Send + 'static,
-        <DbValue as EntityTrait>::Model: FromEntity<Value> + ToEntity<Value>,
+        <DbValue as EntityTrait>::Model: FromEntity<Value> + ToEntity<Value> + Sync,
     {
         let (query, execute) = self.new_carriers();
         ProjectingContainer::from_carriers(self.final_name::<DbValue>("Projector"), query, execute)
     }
 
+    /// Creates a [JoinedContainer] holding rows of `A` together with their
+    /// related `B`, see [JoinedSelect](carrier::joined_query::JoinedSelect).
+    pub fn joined<A, B>(self) -> JoinedContainer<A, B>
+    where
+        A: EntityTrait + Send + 'static,
+        B: EntityTrait + Send + 'static,
+        A::Model: Sync,
+        B::Model: Sync,
+    {
+        let name = self.final_name::<(A, B)>("Joined");
+        let (query, execute) = carrier::both_joined_carriers(
+            self.pool,
+            name.clone(),
+            self.all_tables,
+            self.tables_changed_sender,
+            self.new_register_sender,
+            self.shared_queries,
+        );
+        JoinedContainer::from_carriers(name, query, execute)
+    }
+
     pub fn manual<Value>(self) -> manual::Container<Value>
     where
         Value: Send + 'static,
@@ -76,10 +108,83 @@ impl ContainerBuilder {
             self.all_tables,
             self.tables_changed_sender,
             self.new_register_sender,
+            self.shared_queries,
         );
         manual::Container::from_carriers(name, query, execute)
     }
 
+    /// Creates a [TreeContainer] for a self-referencing table, `key_fn`
+    /// returns the key of a row and `parent_fn` the key of its parent.
+    pub fn tree<DbValue, K>(
+        self,
+        key_fn: impl Fn(&DbValue::Model) -> K + Sync + Send + 'static,
+        parent_fn: impl Fn(&DbValue::Model) -> Option<K> + Sync + Send + 'static,
+    ) -> TreeContainer<DbValue, K>
+    where
+        DbValue: EntityTrait + Send + 'static,
+        DbValue::Model: Sync,
+        K: Clone + Hash + Eq + Sync + Send + 'static,
+    {
+        let (query, execute) = self.new_carriers();
+        TreeContainer::from_carriers(
+            self.final_name::<DbValue>("Tree"),
+            query,
+            execute,
+            Arc::new(key_fn),
+            Arc::new(parent_fn),
+        )
+    }
+
+    /// Creates a [NestedContainer] for parents of type `P` with their
+    /// children, which are set through
+    /// [stored_query](NestedContainer::stored_query).
+    pub fn nested<Value, P, Children>(self) -> NestedContainer<Value, P, Children>
+    where
+        Value: Send + Sync + 'static,
+        P: EntityTrait + Send + Sync + 'static,
+        P::Model: Sync,
+        Children: Clone + Send + 'static,
+    {
+        let name = self.final_name::<P>("Nested");
+        let (query, execute) = carrier::both_manual_carriers(
+            self.pool,
+            name.clone(),
+            self.all_tables.clone(),
+            self.tables_changed_sender,
+            self.new_register_sender.clone(),
+            self.shared_queries,
+        );
+        NestedContainer::from_carriers(
+            name,
+            query,
+            execute,
+            self.all_tables,
+            self.new_register_sender,
+        )
+    }
+
+    /// Creates a [tasked::Container] that is refreshed by the
+    /// [Messenger](crate::messenger::Messenger) whenever one of the passed
+    /// `tables` changes, for fetchers reading the database through some other
+    /// library.
+    pub fn tasked<P, Fut, O, E>(
+        self,
+        task_func: impl Fn(P) -> Fut + Send + Sync + 'static,
+        init_param: P,
+        tables: &[&str],
+    ) -> tasked::Container<P, Fut, O, E>
+    where
+        P: Clone + Send + 'static,
+        Fut: Future<Output = Result<Vec<O>, E>> + Send + 'static,
+        O: Send + 'static,
+        E: Send + 'static,
+    {
+        let name = self.final_name::<O>("Tasked");
+        let invalidation =
+            Invalidation::register(&name, &self.all_tables, tables, self.new_register_sender);
+        tasked::Container::new(name, task_func, init_param).with_invalidation(invalidation)
+    }
+
     fn new_carriers<DbValue>(&self) -> (SimpleQueryCarrier<DbValue>, ExecuteCarrier)
     where
         DbValue: EntityTrait + Send + 'static,
@@ -91,6 +196,7 @@ impl ContainerBuilder {
             self.all_tables.clone(),
             self.tables_changed_sender.clone(),
             self.new_register_sender.clone(),
+            self.shared_queries.clone(),
         )
     }
 }
@@ -1,38 +1,169 @@
-use std::{borrow::Borrow, cmp::Ordering, sync::Arc};
+mod diffing;
+mod filtering;
+pub mod grouping;
+pub mod index;
+pub mod observer;
+pub mod patch;
+pub mod sorting;
+pub mod tree;
 
-use permutation::Permutation;
+use std::{cmp::Ordering, collections::HashMap, hash::Hash, mem, sync::Arc};
+
+use tokio::sync::watch;
+
+use diffing::{AnyRowKey, RowKey};
+use filtering::DataFilters;
+use grouping::{AnyGrouping, Grouping, GroupsView};
+use index::{AnyIndex, Index, IndexView};
+use observer::{DataObserver, Snapshot};
+use patch::DataPatch;
+use sorting::{SortDirection, SortKey, SortSpec};
+use tree::{AnyTree, Tree, TreeNodes, TreeView};
 
 pub struct Data<Value> {
     pub(crate) data: Arc<[Value]>,
     sorting: Option<DataSorting<Value>>,
+    filters: DataFilters<Value>,
+    /// Indices into `data` of all values passing the filters, in sorted order
+    visible: Vec<usize>,
+    groupings: Vec<(String, Box<dyn AnyGrouping<Value>>)>,
+    indexes: Vec<(String, Box<dyn AnyIndex<Value>>)>,
+    tree: Option<Box<dyn AnyTree<Value>>>,
+    row_key: Option<Box<dyn AnyRowKey<Value>>>,
     has_changed: bool,
+    /// Incremented whenever the values change
+    generation: u64,
+    observers: Option<watch::Sender<Snapshot<Value>>>,
 }
 
 impl<Value> Default for Data<Value> {
     fn default() -> Self {
-        Self {
-            data: vec![].into(),
-            sorting: None,
-            has_changed: true,
-        }
+        Self::from(vec![])
     }
 }
 
 impl<Value> From<Vec<Value>> for Data<Value> {
     fn from(value: Vec<Value>) -> Self {
         Self {
+            visible: (0..value.len()).collect(),
             data: value.into(),
             sorting: None,
+            filters: DataFilters::default(),
+            groupings: vec![],
+            indexes: vec![],
+            tree: None,
+            row_key: None,
             has_changed: true,
+            generation: 0,
+            observers: None,
         }
     }
 }
 
 impl<Value> Data<Value> {
     pub(crate) fn set(&mut self, new_data: impl Iterator<Item = Value>) {
-        self.data = new_data.collect::<Vec<_>>().into();
+        self.set_shared(new_data.collect::<Vec<_>>().into());
+    }
+
+    /// Same as [Data::set] but keeps sharing `data`, for example with the
+    /// other containers that ran the same query
+    pub(crate) fn set_shared(&mut self, data: Arc<[Value]>) {
+        match self.row_key.as_ref() {
+            Some(row_key) => {
+                let kept = row_key.match_rows(&self.data, &data);
+                self.replace_rows(data, kept);
+            }
+            None => {
+                self.data = data;
+                self.values_changed();
+                self.reindex();
+                self.resort();
+            }
+        }
+    }
+
+    /// Replaces the values with `data`, where `kept` holds the new position
+    /// of every old row that didn't change. The kept rows keep their sorted
+    /// position and filter result, only the others are sorted and filtered,
+    /// unless most rows changed or the kept rows were reordered.
+    fn replace_rows(&mut self, data: Arc<[Value]>, kept: Vec<Option<usize>>) {
+        let unchanged = data.len() == kept.len()
+            && kept
+                .iter()
+                .enumerate()
+                .all(|(index, new_index)| *new_index == Some(index));
+        if unchanged {
+            self.data = data;
+            return;
+        }
+
+        let mut is_changed = vec![true; data.len()];
+        kept.iter()
+            .flatten()
+            .for_each(|index| is_changed[*index] = false);
+        let changed = (0..data.len())
+            .filter(|index| is_changed[*index])
+            .collect::<Vec<_>>();
+        let incremental = changed.len() <= data.len() / 2 && kept.iter().flatten().is_sorted();
+
+        self.data = data;
+        self.values_changed();
+        self.reindex();
+        match incremental {
+            true => self.merge_changed(&kept, changed),
+            false => self.resort(),
+        }
+    }
+
+    /// Merges the `changed` rows into the sorted order and the visible rows
+    /// of the `kept` ones, see [Data::replace_rows]
+    fn merge_changed(&mut self, kept: &[Option<usize>], mut changed: Vec<usize>) {
+        let data = &self.data;
+        if let Some(sorting) = self.sorting.as_mut() {
+            changed.sort_by(|a, b| sorting.compare_indices(data, *a, *b));
+            let order = sorting.order.iter().filter_map(|index| kept[*index]);
+            let order = merge_sorted(order.collect(), &changed, |a, b| {
+                sorting.compare_indices(data, a, b)
+            });
+            sorting.order = order;
+        }
+
+        let visible = self.visible.iter().filter_map(|index| kept[*index]);
+        changed.retain(|index| self.filters.matches(&data[*index]));
+        self.visible = match self.sorting.as_ref() {
+            Some(sorting) => merge_sorted(visible.collect(), &changed, |a, b| {
+                sorting.compare_indices(data, a, b)
+            }),
+            None => merge_sorted(visible.collect(), &changed, |a, b| a.cmp(&b)),
+        };
+        self.rebuild_views();
+    }
+
+    /// Counts up whenever the values are replaced or patched, unlike
+    /// [ImplData::has_changed] it is not reset by viewing the data
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub(super) fn observe(&mut self) -> DataObserver<Value> {
+        let reciver = match self.observers.as_ref() {
+            Some(observers) => observers.subscribe(),
+            None => {
+                let (sender, reciver) =
+                    watch::channel(Snapshot::new(self.generation, self.data.clone()));
+                let _ = self.observers.insert(sender);
+                reciver
+            }
+        };
+        DataObserver::new(reciver)
+    }
+
+    fn values_changed(&mut self) {
         self.has_changed = true;
-        self.resort();
+        self.generation += 1;
+        if let Some(observers) = self.observers.as_ref() {
+            observers.send_replace(Snapshot::new(self.generation, self.data.clone()));
+        }
     }
 
     pub(crate) fn set_viewed(&mut self) {
@@ -40,55 +171,456 @@ impl<Value> Data<Value> {
     }
 
     pub fn sorted(&self) -> Vec<&Value> {
-        let data_as_ref = self.data.iter().map(Borrow::borrow).collect();
         match self.sorting.as_ref() {
-            Some(sorting) => sorting.permutation.apply_slice(data_as_ref),
-            None => data_as_ref,
+            Some(sorting) => sorting
+                .order
+                .iter()
+                .map(|index| &self.data[*index])
+                .collect(),
+            None => self.data.iter().collect(),
         }
     }
 
-    pub(super) fn new_sorting(
+    /// All values matching the current filters in sorted order. Unlike
+    /// [Data::sorted] this does not allocate, the order is cached whenever
+    /// the data, the sorting or the filters change.
+    pub fn visible(&self) -> impl ExactSizeIterator<Item = &Value> {
+        self.visible.iter().map(|index| &self.data[*index])
+    }
+
+    pub fn sort_spec(&self) -> Option<&SortSpec<Value>> {
+        self.sorting.as_ref().map(|sorting| &sorting.spec)
+    }
+
+    pub(super) fn new_sorting(&mut self, spec: SortSpec<Value>) {
+        self.update_sorting(|current| *current = spec);
+    }
+
+    /// Changes the current [SortSpec] through `change` and resorts the data
+    /// afterwards. An empty spec removes the sorting altogether.
+    pub(super) fn update_sorting<R>(
         &mut self,
-        sorting_fn: impl Fn(&Value, &Value) -> Ordering + Send + Sync + 'static,
-    ) {
-        let mut new_sorting = DataSorting::new(sorting_fn);
-        new_sorting.resort(&self.data);
-        let _ = self.sorting.insert(new_sorting);
+        change: impl FnOnce(&mut SortSpec<Value>) -> R,
+    ) -> R {
+        let mut sorting = self.sorting.take().unwrap_or_default();
+        let result = change(&mut sorting.spec);
+        if !sorting.spec.is_empty() {
+            let _ = self.sorting.insert(sorting);
+        }
+        self.resort();
+        result
+    }
+
+    /// Changes the filters through `change` and recomputes the visible values
+    pub(super) fn update_filters<R>(
+        &mut self,
+        change: impl FnOnce(&mut DataFilters<Value>) -> R,
+    ) -> R {
+        let result = change(&mut self.filters);
+        self.refilter();
+        result
+    }
+
+    pub fn filter_names(&self) -> impl Iterator<Item = &str> {
+        self.filters.names()
+    }
+
+    /// Registers a grouping of the visible values, or replaces the one with
+    /// the same name. The groups are recomputed whenever the data, the sorting
+    /// or the filters change.
+    pub(super) fn new_grouping<K>(
+        &mut self,
+        name: String,
+        key_fn: impl Fn(&Value) -> K + Sync + Send + 'static,
+    ) where
+        K: Ord + Sync + Send + 'static,
+        Value: 'static,
+    {
+        let mut grouping = Grouping::new(key_fn);
+        grouping.regroup(&self.data, &self.visible);
+        match self.groupings.iter_mut().find(|(other, _)| *other == name) {
+            Some((_, current)) => *current = Box::new(grouping),
+            None => self.groupings.push((name, Box::new(grouping))),
+        }
+    }
+
+    pub(super) fn remove_grouping(&mut self, name: &str) -> bool {
+        let len_before = self.groupings.len();
+        self.groupings.retain(|(other, _)| other != name);
+        len_before != self.groupings.len()
+    }
+
+    /// Returns the groups of the grouping registered under `name`. Returns
+    /// `None` if there is no such grouping or it was registered with a
+    /// different key type.
+    pub fn groups<K>(&self, name: &str) -> Option<GroupsView<'_, K, Value>>
+    where
+        K: Ord + 'static,
+    {
+        let (_, grouping) = self.groupings.iter().find(|(other, _)| other == name)?;
+        let groups = grouping
+            .groups()
+            .downcast_ref::<Vec<grouping::Group<K>>>()?;
+        Some(GroupsView::new(groups, &self.data))
+    }
+
+    /// Registers a secondary index over all values, or replaces the one with
+    /// the same name. The index is rebuilt whenever the data changes.
+    pub(super) fn new_index<K>(
+        &mut self,
+        name: String,
+        key_fn: impl Fn(&Value) -> K + Sync + Send + 'static,
+    ) where
+        K: Hash + Eq + Sync + Send + 'static,
+        Value: 'static,
+    {
+        let mut index = Index::new(key_fn);
+        index.rebuild(&self.data);
+        match self.indexes.iter_mut().find(|(other, _)| *other == name) {
+            Some((_, current)) => *current = Box::new(index),
+            None => self.indexes.push((name, Box::new(index))),
+        }
+    }
+
+    pub(super) fn remove_index(&mut self, name: &str) -> bool {
+        let len_before = self.indexes.len();
+        self.indexes.retain(|(other, _)| other != name);
+        len_before != self.indexes.len()
+    }
+
+    /// Returns the index registered under `name`. Returns `None` if there is
+    /// no such index or it was registered with a different key type.
+    pub fn index<K>(&self, name: &str) -> Option<IndexView<'_, K, Value>>
+    where
+        K: Hash + Eq + 'static,
+    {
+        let (_, index) = self.indexes.iter().find(|(other, _)| other == name)?;
+        let entries = index.entries().downcast_ref::<HashMap<K, Vec<usize>>>()?;
+        Some(IndexView::new(entries, &self.data))
+    }
+
+    /// Sets up the tree of the values, replacing any previous one. The tree
+    /// is rebuilt whenever the data, the sorting or the filters change.
+    pub(super) fn set_tree<K>(
+        &mut self,
+        key_fn: impl Fn(&Value) -> K + Sync + Send + 'static,
+        parent_fn: impl Fn(&Value) -> Option<K> + Sync + Send + 'static,
+    ) where
+        K: Clone + Eq + Hash + Sync + Send + 'static,
+        Value: 'static,
+    {
+        let mut tree = Tree::new(key_fn, parent_fn);
+        tree.rebuild(&self.data, &self.visible);
+        let _ = self.tree.insert(Box::new(tree));
+    }
+
+    /// Returns the tree set up with [ImplData::tree_by], `None` if there is
+    /// none or it has a different key type
+    pub fn tree<K>(&self) -> Option<TreeView<'_, K, Value>>
+    where
+        K: Eq + Hash + 'static,
+    {
+        let nodes = self.tree.as_ref()?.nodes().downcast_ref::<TreeNodes<K>>()?;
+        Some(TreeView::new(nodes, &self.data))
+    }
+
+    pub(super) fn tree_nodes_mut<K>(&mut self) -> Option<&mut TreeNodes<K>>
+    where
+        K: 'static,
+    {
+        self.tree
+            .as_mut()?
+            .nodes_mut()
+            .downcast_mut::<TreeNodes<K>>()
+    }
+
+    pub(super) fn set_row_key<K>(&mut self, key_fn: impl Fn(&Value) -> K + Sync + Send + 'static)
+    where
+        K: Hash + Eq + Sync + Send + 'static,
+        Value: PartialEq + 'static,
+    {
+        let _ = self.row_key.insert(Box::new(RowKey::new(key_fn)));
+    }
+
+    fn reindex(&mut self) {
+        for (_, index) in self.indexes.iter_mut() {
+            index.rebuild(&self.data);
+        }
     }
 
     fn resort(&mut self) {
         if let Some(soring) = self.sorting.as_mut() {
             soring.resort(&self.data)
         }
+        self.refilter();
+    }
+
+    fn refilter(&mut self) {
+        let in_order = (0..self.data.len()).map(|position| match self.sorting.as_ref() {
+            Some(sorting) => sorting.order[position],
+            None => position,
+        });
+        self.visible = match self.filters.is_empty() {
+            true => in_order.collect(),
+            false => in_order
+                .filter(|index| self.filters.matches(&self.data[*index]))
+                .collect(),
+        };
+        self.rebuild_views();
+    }
+
+    /// Rebuilds everything computed from the visible values
+    fn rebuild_views(&mut self) {
+        for (_, grouping) in self.groupings.iter_mut() {
+            grouping.regroup(&self.data, &self.visible);
+        }
+        if let Some(tree) = self.tree.as_mut() {
+            tree.rebuild(&self.data, &self.visible);
+        }
     }
 }
 
+impl<Value> Data<Value>
+where
+    Value: Clone,
+{
+    /// Applies row level changes to the data. Only the touched rows are
+    /// sorted and filtered, all other rows keep their position. Patches that
+    /// only update rows change them in place, as long as no
+    /// [observer](ImplData::observe) holds on to the current values. Updates
+    /// of rows that the patch also removes are dropped.
+    ///
+    /// Panics if an index of an update or removal is out of bounds.
+    pub fn patch(&mut self, patch: DataPatch<Value>) {
+        if patch.is_empty() {
+            return;
+        }
+        let DataPatch {
+            updates,
+            mut removals,
+            inserts,
+        } = patch;
+        let len = self.data.len();
+        let indices = updates.iter().map(|(index, _)| index).chain(&removals);
+        if let Some(index) = indices.copied().find(|index| *index >= len) {
+            panic!("patch index {index} is out of bounds for {len} rows");
+        }
+        removals.sort_unstable();
+        removals.dedup();
+
+        let mut removed = 0;
+        let mut kept = (0..self.data.len())
+            .map(|index| match removals.binary_search(&index) {
+                Ok(_) => {
+                    removed += 1;
+                    None
+                }
+                Err(_) => Some(index - removed),
+            })
+            .collect::<Vec<_>>();
+
+        let data = match removals.is_empty() && inserts.is_empty() {
+            true => {
+                let mut data = mem::replace(&mut self.data, Vec::new().into());
+                let values = Arc::make_mut(&mut data);
+                for (index, value) in updates {
+                    values[index] = value;
+                    kept[index] = None;
+                }
+                data
+            }
+            false => {
+                let mut updates = updates.into_iter().collect::<HashMap<_, _>>();
+                let mut values = Vec::with_capacity(self.data.len() - removed + inserts.len());
+                for (index, value) in self.data.iter().enumerate() {
+                    if kept[index].is_none() {
+                        continue;
+                    }
+                    match updates.remove(&index) {
+                        Some(updated) => {
+                            values.push(updated);
+                            kept[index] = None;
+                        }
+                        None => values.push(value.clone()),
+                    }
+                }
+                values.extend(inserts);
+                values.into()
+            }
+        };
+        self.replace_rows(data, kept);
+    }
+
+    /// Appends rows, e.g. further rows of a streaming query. The existing
+    /// values are copied into a new allocation, but only the appended rows
+    /// are indexed, sorted and filtered, regardless of how many there are.
+    /// Streaming queries hold back rows until they at least double the data,
+    /// so that the copies add up to linear time over a stream.
+    pub fn append(&mut self, values: Vec<Value>) {
+        if values.is_empty() {
+            return;
+        }
+        let len = self.data.len();
+        self.data = self.data.iter().cloned().chain(values).collect();
+        self.values_changed();
+        for (_, index) in self.indexes.iter_mut() {
+            index.extend(&self.data, len);
+        }
+        let kept = (0..len).map(Some).collect::<Vec<_>>();
+        self.merge_changed(&kept, (len..self.data.len()).collect());
+    }
+}
+
+/// Merges the sorted `changed` indices into the already sorted `kept` ones,
+/// finding their positions by binary search so that only the changed values
+/// are compared
+fn merge_sorted(
+    kept: Vec<usize>,
+    changed: &[usize],
+    compare: impl Fn(usize, usize) -> Ordering,
+) -> Vec<usize> {
+    let mut merged = Vec::with_capacity(kept.len() + changed.len());
+    let mut start = 0;
+    for index in changed {
+        let position =
+            start + kept[start..].partition_point(|probe| compare(*probe, *index).is_lt());
+        merged.extend_from_slice(&kept[start..position]);
+        merged.push(*index);
+        start = position;
+    }
+    merged.extend_from_slice(&kept[start..]);
+    merged
+}
+
 struct DataSorting<Value> {
-    permutation: Permutation,
-    sorting_fn: SortingFn<Value>,
+    /// Indices into the data in sorted order. Equal values are ordered by
+    /// their index, which keeps the order identical to a stable sort.
+    order: Vec<usize>,
+    spec: SortSpec<Value>,
 }
 
-impl<Value> DataSorting<Value> {
-    fn new(soring_fn: impl Fn(&Value, &Value) -> Ordering + Sync + Send + 'static) -> Self {
+impl<Value> Default for DataSorting<Value> {
+    fn default() -> Self {
         Self {
-            permutation: permutation::sort_by(Vec::<Value>::new(), &soring_fn),
-            sorting_fn: Box::new(soring_fn),
+            order: vec![],
+            spec: SortSpec::default(),
         }
     }
+}
 
+impl<Value> DataSorting<Value> {
     fn resort(&mut self, data: &[Value]) {
-        self.permutation = permutation::sort_by(data, |a, b| (self.sorting_fn)(a, b))
+        self.order = (0..data.len()).collect();
+        self.order
+            .sort_by(|a, b| self.spec.compare(&data[*a], &data[*b]));
     }
-}
 
-type SortingFn<Value> = Box<dyn Fn(&Value, &Value) -> Ordering + Sync + Send + 'static>;
+    fn compare_indices(&self, data: &[Value], a: usize, b: usize) -> Ordering {
+        self.spec.compare(&data[a], &data[b]).then(a.cmp(&b))
+    }
+}
 
 pub trait ImplData<Value> {
     fn data(&self) -> &Arc<[Value]>;
+    /// Sorts by a single comparator, replacing any previous sorting. The key
+    /// is named [SortKey::DEFAULT_NAME].
     fn sort(&mut self, sorting_fn: impl Fn(&Value, &Value) -> Ordering + Sync + Send + 'static);
+    /// Replaces the current sorting with the passed [SortSpec]
+    fn sort_by_spec(&mut self, spec: SortSpec<Value>);
+    /// Adds a tie breaking key to the current sorting, see [SortSpec::push]
+    fn push_sort_key(&mut self, key: SortKey<Value>);
+    /// Only sort by the passed key from now on, see [SortSpec::replace]
+    fn replace_sort_key(&mut self, key: SortKey<Value>);
+    /// Flips the direction of the named key, see [SortSpec::toggle]
+    fn toggle_sort_direction(&mut self, name: &str) -> Option<SortDirection>;
+    fn clear_sorting(&mut self);
+    fn sort_spec(&self) -> Option<&SortSpec<Value>>;
     fn sorted(&self) -> Vec<&Value>;
+    /// Filters by a single unnamed predicate, keeping all named filters
+    fn filter(&mut self, predicate: impl Fn(&Value) -> bool + Sync + Send + 'static);
+    /// Adds a named filter or replaces the one with the same name. Values are
+    /// only visible if they match all filters.
+    fn set_filter(
+        &mut self,
+        name: impl Into<String>,
+        predicate: impl Fn(&Value) -> bool + Sync + Send + 'static,
+    );
+    /// Removes the named filter, returns `false` if there was none
+    fn remove_filter(&mut self, name: &str) -> bool;
+    fn clear_filters(&mut self);
+    /// All values passing the filters in sorted order, see [Data::visible]
+    fn visible<'a>(&'a self) -> impl ExactSizeIterator<Item = &'a Value>
+    where
+        Value: 'a;
+    /// Registers a named grouping of the visible values by `key_fn`, see
+    /// [Data::groups] for reading the groups
+    fn group_by<K>(
+        &mut self,
+        name: impl Into<String>,
+        key_fn: impl Fn(&Value) -> K + Sync + Send + 'static,
+    ) where
+        K: Ord + Sync + Send + 'static,
+        Value: 'static;
+    fn groups<K>(&self, name: &str) -> Option<GroupsView<'_, K, Value>>
+    where
+        K: Ord + 'static;
+    fn remove_grouping(&mut self, name: &str) -> bool;
+    /// Registers a named secondary index over all values by `key_fn`, see
+    /// [Data::index] for lookups
+    fn index_by<K>(
+        &mut self,
+        name: impl Into<String>,
+        key_fn: impl Fn(&Value) -> K + Sync + Send + 'static,
+    ) where
+        K: Hash + Eq + Sync + Send + 'static,
+        Value: 'static;
+    fn index<K>(&self, name: &str) -> Option<IndexView<'_, K, Value>>
+    where
+        K: Hash + Eq + 'static;
+    fn remove_index(&mut self, name: &str) -> bool;
+    /// Arranges the values as a tree of values pointing to their parent's
+    /// key, see [Data::tree] for reading it. Which nodes are expanded is
+    /// kept by key, so it survives refreshes and resorting.
+    fn tree_by<K>(
+        &mut self,
+        key_fn: impl Fn(&Value) -> K + Sync + Send + 'static,
+        parent_fn: impl Fn(&Value) -> Option<K> + Sync + Send + 'static,
+    ) where
+        K: Clone + Hash + Eq + Sync + Send + 'static,
+        Value: 'static;
+    fn tree<K>(&self) -> Option<TreeView<'_, K, Value>>
+    where
+        K: Hash + Eq + 'static;
+    /// Returns `false` if there is no tree with keys of type `K`, in which
+    /// case nothing is expanded
+    fn expand<K>(&mut self, key: K) -> bool
+    where
+        K: Clone + Hash + Eq + 'static;
+    /// Returns `false` if there is no tree with keys of type `K`
+    fn collapse<K>(&mut self, key: &K) -> bool
+    where
+        K: Clone + Hash + Eq + 'static;
+    /// Returns if the node is expanded afterwards
+    fn toggle_expanded<K>(&mut self, key: K) -> bool
+    where
+        K: Clone + Hash + Eq + 'static;
+    fn expand_all(&mut self);
+    fn collapse_all(&mut self);
+    /// Identifies rows by `key_fn`, so that a refresh only sorts and filters
+    /// the rows that are new or not equal to the row with the same key before.
+    /// A refresh that changes nothing keeps the [generation](Data::generation).
+    fn key_by<K>(&mut self, key_fn: impl Fn(&Value) -> K + Sync + Send + 'static)
+    where
+        K: Hash + Eq + Sync + Send + 'static,
+        Value: PartialEq + 'static;
     fn has_changed(&self) -> bool;
     fn set_viewed(&mut self) -> &mut Self;
+    /// See [Data::generation]
+    fn generation(&self) -> u64;
+    /// Returns an observer that always sees the current values, for deriving
+    /// other data from them, see
+    /// [DerivedContainer](crate::container::derived::DerivedContainer)
+    fn observe(&mut self) -> DataObserver<Value>;
 }
 
 impl<T, Value> ImplData<Value> for T
@@ -99,11 +631,152 @@ where
         &self.ref_data().data
     }
     fn sort(&mut self, sorting_fn: impl Fn(&Value, &Value) -> Ordering + Sync + Send + 'static) {
-        self.ref_mut_data().new_sorting(sorting_fn);
+        self.ref_mut_data()
+            .new_sorting(SortKey::by(SortKey::<Value>::DEFAULT_NAME, sorting_fn).into());
+    }
+    fn sort_by_spec(&mut self, spec: SortSpec<Value>) {
+        self.ref_mut_data().new_sorting(spec);
+    }
+    fn push_sort_key(&mut self, key: SortKey<Value>) {
+        self.ref_mut_data().update_sorting(|spec| spec.push(key));
+    }
+    fn replace_sort_key(&mut self, key: SortKey<Value>) {
+        self.ref_mut_data().update_sorting(|spec| spec.replace(key));
+    }
+    fn toggle_sort_direction(&mut self, name: &str) -> Option<SortDirection> {
+        self.ref_mut_data().update_sorting(|spec| spec.toggle(name))
+    }
+    fn clear_sorting(&mut self) {
+        self.ref_mut_data().update_sorting(SortSpec::clear);
+    }
+    fn sort_spec(&self) -> Option<&SortSpec<Value>> {
+        self.ref_data().sort_spec()
     }
     fn sorted(&self) -> Vec<&Value> {
         self.ref_data().sorted()
     }
+    fn filter(&mut self, predicate: impl Fn(&Value) -> bool + Sync + Send + 'static) {
+        self.set_filter(String::new(), predicate);
+    }
+    fn set_filter(
+        &mut self,
+        name: impl Into<String>,
+        predicate: impl Fn(&Value) -> bool + Sync + Send + 'static,
+    ) {
+        let name = name.into();
+        self.ref_mut_data()
+            .update_filters(|filters| filters.set(name, Box::new(predicate)));
+    }
+    fn remove_filter(&mut self, name: &str) -> bool {
+        self.ref_mut_data()
+            .update_filters(|filters| filters.remove(name))
+    }
+    fn clear_filters(&mut self) {
+        self.ref_mut_data().update_filters(DataFilters::clear);
+    }
+    fn visible<'a>(&'a self) -> impl ExactSizeIterator<Item = &'a Value>
+    where
+        Value: 'a,
+    {
+        self.ref_data().visible()
+    }
+    fn group_by<K>(
+        &mut self,
+        name: impl Into<String>,
+        key_fn: impl Fn(&Value) -> K + Sync + Send + 'static,
+    ) where
+        K: Ord + Sync + Send + 'static,
+        Value: 'static,
+    {
+        self.ref_mut_data().new_grouping(name.into(), key_fn);
+    }
+    fn groups<K>(&self, name: &str) -> Option<GroupsView<'_, K, Value>>
+    where
+        K: Ord + 'static,
+    {
+        self.ref_data().groups(name)
+    }
+    fn remove_grouping(&mut self, name: &str) -> bool {
+        self.ref_mut_data().remove_grouping(name)
+    }
+    fn index_by<K>(
+        &mut self,
+        name: impl Into<String>,
+        key_fn: impl Fn(&Value) -> K + Sync + Send + 'static,
+    ) where
+        K: Hash + Eq + Sync + Send + 'static,
+        Value: 'static,
+    {
+        self.ref_mut_data().new_index(name.into(), key_fn);
+    }
+    fn index<K>(&self, name: &str) -> Option<IndexView<'_, K, Value>>
+    where
+        K: Hash + Eq + 'static,
+    {
+        self.ref_data().index(name)
+    }
+    fn remove_index(&mut self, name: &str) -> bool {
+        self.ref_mut_data().remove_index(name)
+    }
+    fn tree_by<K>(
+        &mut self,
+        key_fn: impl Fn(&Value) -> K + Sync + Send + 'static,
+        parent_fn: impl Fn(&Value) -> Option<K> + Sync + Send + 'static,
+    ) where
+        K: Clone + Hash + Eq + Sync + Send + 'static,
+        Value: 'static,
+    {
+        self.ref_mut_data().set_tree(key_fn, parent_fn);
+    }
+    fn tree<K>(&self) -> Option<TreeView<'_, K, Value>>
+    where
+        K: Hash + Eq + 'static,
+    {
+        self.ref_data().tree()
+    }
+    fn expand<K>(&mut self, key: K) -> bool
+    where
+        K: Clone + Hash + Eq + 'static,
+    {
+        self.ref_mut_data()
+            .tree_nodes_mut()
+            .map(|nodes| nodes.expand(key))
+            .is_some()
+    }
+    fn collapse<K>(&mut self, key: &K) -> bool
+    where
+        K: Clone + Hash + Eq + 'static,
+    {
+        self.ref_mut_data()
+            .tree_nodes_mut()
+            .map(|nodes| nodes.collapse(key))
+            .is_some()
+    }
+    fn toggle_expanded<K>(&mut self, key: K) -> bool
+    where
+        K: Clone + Hash + Eq + 'static,
+    {
+        self.ref_mut_data()
+            .tree_nodes_mut()
+            .is_some_and(|nodes| nodes.toggle(key))
+    }
+    fn expand_all(&mut self) {
+        if let Some(tree) = self.ref_mut_data().tree.as_mut() {
+            tree.expand_all();
+        }
+    }
+    fn collapse_all(&mut self) {
+        if let Some(tree) = self.ref_mut_data().tree.as_mut() {
+            tree.collapse_all();
+        }
+    }
+    fn key_by<K>(&mut self, key_fn: impl Fn(&Value) -> K + Sync + Send + 'static)
+    where
+        K: Hash + Eq + Sync + Send + 'static,
+        Value: PartialEq + 'static,
+    {
+        self.ref_mut_data().set_row_key(key_fn);
+    }
     fn has_changed(&self) -> bool {
         self.ref_data().has_changed
     }
@@ -111,6 +784,12 @@ where
         self.ref_mut_data().set_viewed();
         self
     }
+    fn generation(&self) -> u64 {
+        self.ref_data().generation
+    }
+    fn observe(&mut self) -> DataObserver<Value> {
+        self.ref_mut_data().observe()
+    }
 }
 
 pub(crate) trait HasData<Value> {
@@ -127,3 +806,298 @@ impl<Value> HasData<Value> for Data<Value> {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::container::data::{
+        patch::DataPatch,
+        sorting::{SortDirection, SortKey},
+        Data, ImplData,
+    };
+
+    fn sorted_pairs(data: &Data<(u8, u8)>) -> Vec<(u8, u8)> {
+        data.sorted().into_iter().copied().collect()
+    }
+
+    #[test]
+    fn composite_keys_break_ties_in_order() {
+        let mut data = Data::from(vec![(2, 1), (1, 1), (2, 3), (1, 2)]);
+        data.push_sort_key(SortKey::by_key("first", |v: &(u8, u8)| v.0));
+        data.push_sort_key(SortKey::by_key("second", |v: &(u8, u8)| v.1).descending());
+
+        assert_eq!(vec![(1, 2), (1, 1), (2, 3), (2, 1)], sorted_pairs(&data));
+    }
+
+    #[test]
+    fn toggling_direction_resorts() {
+        let mut data = Data::from(vec![(1, 0), (3, 0), (2, 0)]);
+        data.replace_sort_key(SortKey::by_key("first", |v: &(u8, u8)| v.0));
+
+        assert_eq!(
+            Some(SortDirection::Descending),
+            data.toggle_sort_direction("first")
+        );
+        assert_eq!(vec![(3, 0), (2, 0), (1, 0)], sorted_pairs(&data));
+        assert_eq!(None, data.toggle_sort_direction("missing"));
+    }
+
+    #[test]
+    fn sorting_is_kept_across_set_and_clear_removes_it() {
+        let mut data = Data::from(vec![]);
+        data.replace_sort_key(SortKey::by_key("first", |v: &(u8, u8)| v.0).descending());
+        data.set(vec![(1, 0), (2, 0)].into_iter());
+        assert_eq!(vec![(2, 0), (1, 0)], sorted_pairs(&data));
+
+        data.clear_sorting();
+        assert!(data.sort_spec().is_none());
+        assert_eq!(vec![(1, 0), (2, 0)], sorted_pairs(&data));
+    }
+
+    #[test]
+    fn visible_combines_filters_and_sorting() {
+        let mut data = Data::from(vec![(3, 1), (1, 0), (4, 1), (2, 1)]);
+        data.set_filter("ones", |v: &(u8, u8)| v.1 == 1);
+        data.filter(|v: &(u8, u8)| v.0 > 2);
+        data.replace_sort_key(SortKey::by_key("first", |v: &(u8, u8)| v.0).descending());
+
+        assert_eq!(
+            vec![(4, 1), (3, 1)],
+            data.visible().copied().collect::<Vec<_>>()
+        );
+
+        assert!(data.remove_filter(""));
+        assert!(!data.remove_filter(""));
+        data.set(vec![(5, 1), (6, 0), (0, 1)].into_iter());
+        assert_eq!(
+            vec![(5, 1), (0, 1)],
+            data.visible().copied().collect::<Vec<_>>()
+        );
+
+        data.clear_filters();
+        assert_eq!(3, data.visible().len());
+    }
+
+    #[test]
+    fn incremental_patch_matches_full_sort() {
+        let values: Vec<_> = (0..200u8).map(|v| (v.wrapping_mul(37) % 11, v)).collect();
+        let mut data = Data::from(values);
+        data.set_filter("odd", |v: &(u8, u8)| v.1 % 2 == 1);
+        data.replace_sort_key(SortKey::by_key("first", |v: &(u8, u8)| v.0).descending());
+
+        data.patch(
+            DataPatch::new()
+                .update(3, (10, 3))
+                .update(150, (0, 151))
+                .remove(0)
+                .remove(199)
+                .remove(42)
+                .insert((5, 201))
+                .insert((5, 203)),
+        );
+
+        let mut expected = data.data().to_vec();
+        expected.sort_by_key(|v| std::cmp::Reverse(v.0));
+        assert_eq!(expected, sorted_pairs(&data));
+        assert_eq!(
+            expected
+                .into_iter()
+                .filter(|v| v.1 % 2 == 1)
+                .collect::<Vec<_>>(),
+            data.visible().copied().collect::<Vec<_>>()
+        );
+        assert_eq!(199, data.data().len());
+        assert_eq!((10, 3), data.data()[2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn patch_with_inserts_checks_indices() {
+        let mut data = Data::from(vec![(1, 0)]);
+        data.patch(DataPatch::new().remove(1).insert((2, 0)));
+    }
+
+    #[test]
+    fn groups_follow_sorting_filters_and_data() {
+        let mut data = Data::from(vec![(2, 1), (1, 2), (2, 3), (1, 4), (3, 5)]);
+        data.replace_sort_key(SortKey::by_key("second", |v: &(u8, u8)| v.1).descending());
+        data.group_by("first", |v: &(u8, u8)| v.0);
+
+        let groups = data.groups::<u8>("first").unwrap();
+        assert_eq!(
+            vec![(&1, 2), (&2, 2), (&3, 1)],
+            groups.counts().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![(2, 3), (2, 1)],
+            groups
+                .get(&2)
+                .unwrap()
+                .values()
+                .copied()
+                .collect::<Vec<_>>()
+        );
+        assert!(data.groups::<u16>("first").is_none());
+
+        data.set_filter("small", |v: &(u8, u8)| v.1 < 4);
+        data.patch(DataPatch::new().insert((4, 0)));
+        let groups = data.groups::<u8>("first").unwrap();
+        assert_eq!(
+            vec![&1, &2, &4],
+            groups.iter().map(|group| group.key()).collect::<Vec<_>>()
+        );
+
+        assert!(data.remove_grouping("first"));
+        assert!(data.groups::<u8>("first").is_none());
+    }
+
+    #[test]
+    fn index_looks_up_by_key_and_follows_data() {
+        let mut data = Data::from(vec![(1, 10), (2, 20), (1, 30)]);
+        data.index_by("first", |v: &(u8, u8)| v.0);
+
+        let index = data.index::<u8>("first").unwrap();
+        assert_eq!(
+            vec![(1, 10), (1, 30)],
+            index.get(&1).copied().collect::<Vec<_>>()
+        );
+        assert_eq!(0, index.get(&3).len());
+        assert_eq!(2, index.len());
+
+        data.patch(DataPatch::new().remove(0).insert((3, 40)));
+        let index = data.index::<u8>("first").unwrap();
+        assert_eq!(1, index.count(&1));
+        assert_eq!(Some(&(3, 40)), index.first(&3));
+        assert!(data.index::<u16>("first").is_none());
+        assert!(data.remove_index("first"));
+    }
+
+    #[test]
+    fn tree_follows_sorting_and_keeps_expansion() {
+        // (id, parent)
+        let mut data = Data::from(vec![(1, 0), (3, 1), (2, 1), (4, 3), (5, 9)]);
+        data.tree_by(|v: &(u8, u8)| v.0, |v| Some(v.1));
+        data.sort(|a, b| a.0.cmp(&b.0));
+
+        let flat_ids = |data: &Data<(u8, u8)>| {
+            data.tree::<u8>()
+                .unwrap()
+                .flatten()
+                .iter()
+                .map(|node| (node.node.key().to_owned(), node.depth))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![(1, 0), (5, 0)], flat_ids(&data));
+
+        data.expand_all();
+        assert_eq!(
+            vec![(1, 0), (2, 1), (3, 1), (4, 2), (5, 0)],
+            flat_ids(&data)
+        );
+
+        assert!(data.collapse(&3u8));
+        assert!(!data.collapse(&3u16));
+        data.sort(|a, b| b.0.cmp(&a.0));
+        assert_eq!(vec![(5, 0), (1, 0), (3, 1), (2, 1)], flat_ids(&data));
+
+        data.filter(|v| v.0 != 1);
+        data.set(vec![(1, 0), (3, 1), (6, 3)].into_iter());
+        let tree = data.tree::<u8>().unwrap();
+        assert_eq!(0, tree.roots().len());
+        assert!(!tree.is_expanded(&3));
+        assert!(data.toggle_expanded(3u8));
+        assert_eq!(
+            vec![6],
+            data.tree::<u8>()
+                .unwrap()
+                .get(&3)
+                .unwrap()
+                .children()
+                .map(|node| node.value().0)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn keyed_refresh_only_sorts_changed_rows() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let rows = |changed: u16| {
+            (0..1000u16)
+                .map(|id| (id, if id == 500 { changed } else { id * 7 % 1000 }))
+                .collect::<Vec<_>>()
+        };
+        let comparisons = Arc::new(AtomicUsize::new(0));
+        let mut data = Data::from(rows(0));
+        data.key_by(|v: &(u16, u16)| v.0);
+        data.filter(|v| v.1 % 2 == 0);
+        let counter = comparisons.clone();
+        data.sort(move |a, b| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            a.1.cmp(&b.1)
+        });
+
+        comparisons.store(0, Ordering::Relaxed);
+        data.set(rows(4000).into_iter());
+        assert!(comparisons.load(Ordering::Relaxed) < 100);
+
+        let mut expected = rows(4000);
+        expected.sort_by_key(|v| v.1);
+        assert_eq!(
+            expected,
+            data.sorted().into_iter().copied().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            expected
+                .into_iter()
+                .filter(|v| v.1 % 2 == 0)
+                .collect::<Vec<_>>(),
+            data.visible().copied().collect::<Vec<_>>()
+        );
+
+        let generation = data.generation();
+        data.set(rows(4000).into_iter());
+        assert_eq!(generation, data.generation());
+    }
+
+    #[test]
+    fn append_only_sorts_appended_rows() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let rows = |ids: std::ops::Range<u32>| ids.map(|id| id * 7 % 1000).collect::<Vec<_>>();
+        let comparisons = Arc::new(AtomicUsize::new(0));
+        let mut data = Data::from(rows(0..500));
+        data.filter(|v| v % 2 == 0);
+        let counter = comparisons.clone();
+        data.sort(move |a, b| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            a.cmp(b)
+        });
+
+        data.index_by("value", |v: &u32| *v);
+        comparisons.store(0, Ordering::Relaxed);
+        data.append(rows(500..510));
+        let index = data.index::<u32>("value").unwrap();
+        assert_eq!(index.count(&(509 * 7 % 1000)), 1);
+        assert!(comparisons.load(Ordering::Relaxed) < 200);
+
+        let mut expected = rows(0..510);
+        expected.sort();
+        assert_eq!(
+            expected,
+            data.sorted().into_iter().copied().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            expected
+                .into_iter()
+                .filter(|v| v % 2 == 0)
+                .collect::<Vec<_>>(),
+            data.visible().copied().collect::<Vec<_>>()
+        );
+    }
+}
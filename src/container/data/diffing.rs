@@ -0,0 +1,46 @@
+use std::{collections::HashMap, hash::Hash};
+
+type KeyFn<Value, K> = Box<dyn Fn(&Value) -> K + Sync + Send + 'static>;
+
+/// Type erased row key, so that [Data](super::Data) doesn't need to know the
+/// key type.
+pub(crate) trait AnyRowKey<Value>: Sync + Send {
+    /// For every row of `old` the position of the row in `new` with the same
+    /// key, if there is one and it is still equal
+    fn match_rows(&self, old: &[Value], new: &[Value]) -> Vec<Option<usize>>;
+}
+
+pub(crate) struct RowKey<K, Value> {
+    key_fn: KeyFn<Value, K>,
+}
+
+impl<K, Value> RowKey<K, Value> {
+    pub fn new(key_fn: impl Fn(&Value) -> K + Sync + Send + 'static) -> Self {
+        Self {
+            key_fn: Box::new(key_fn),
+        }
+    }
+}
+
+impl<K, Value> AnyRowKey<Value> for RowKey<K, Value>
+where
+    K: Hash + Eq + Sync + Send + 'static,
+    Value: PartialEq,
+{
+    fn match_rows(&self, old: &[Value], new: &[Value]) -> Vec<Option<usize>> {
+        let mut positions = old
+            .iter()
+            .enumerate()
+            .map(|(index, value)| ((self.key_fn)(value), index))
+            .collect::<HashMap<_, _>>();
+        let mut kept = vec![None; old.len()];
+        for (new_index, value) in new.iter().enumerate() {
+            if let Some(index) = positions.remove(&(self.key_fn)(value)) {
+                if old[index] == *value {
+                    kept[index] = Some(new_index);
+                }
+            }
+        }
+        kept
+    }
+}
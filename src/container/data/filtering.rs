@@ -0,0 +1,44 @@
+pub(crate) type FilterFn<Value> = Box<dyn Fn(&Value) -> bool + Sync + Send + 'static>;
+
+/// Set of named predicates, a value is only visible if it matches all of them.
+pub(crate) struct DataFilters<Value> {
+    filters: Vec<(String, FilterFn<Value>)>,
+}
+
+impl<Value> Default for DataFilters<Value> {
+    fn default() -> Self {
+        Self { filters: vec![] }
+    }
+}
+
+impl<Value> DataFilters<Value> {
+    /// Adds the filter or replaces the one with the same name
+    pub fn set(&mut self, name: String, predicate: FilterFn<Value>) {
+        match self.filters.iter_mut().find(|(other, _)| *other == name) {
+            Some((_, current)) => *current = predicate,
+            None => self.filters.push((name, predicate)),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len_before = self.filters.len();
+        self.filters.retain(|(other, _)| other != name);
+        len_before != self.filters.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.filters.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.filters.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn matches(&self, value: &Value) -> bool {
+        self.filters.iter().all(|(_, predicate)| predicate(value))
+    }
+}
@@ -0,0 +1,131 @@
+use std::{any::Any, collections::BTreeMap};
+
+type KeyFn<Value, K> = Box<dyn Fn(&Value) -> K + Sync + Send + 'static>;
+
+/// Type erased grouping so that groupings with different key types can be
+/// stored next to each other.
+pub(crate) trait AnyGrouping<Value>: Sync + Send {
+    fn regroup(&mut self, data: &[Value], visible: &[usize]);
+    fn groups(&self) -> &dyn Any;
+}
+
+pub(crate) struct Grouping<K, Value> {
+    key_fn: KeyFn<Value, K>,
+    groups: Vec<Group<K>>,
+}
+
+impl<K, Value> Grouping<K, Value> {
+    pub fn new(key_fn: impl Fn(&Value) -> K + Sync + Send + 'static) -> Self {
+        Self {
+            key_fn: Box::new(key_fn),
+            groups: vec![],
+        }
+    }
+}
+
+impl<K, Value> AnyGrouping<Value> for Grouping<K, Value>
+where
+    K: Ord + Sync + Send + 'static,
+{
+    fn regroup(&mut self, data: &[Value], visible: &[usize]) {
+        let mut groups = BTreeMap::<K, Vec<usize>>::new();
+        for index in visible {
+            groups
+                .entry((self.key_fn)(&data[*index]))
+                .or_default()
+                .push(*index);
+        }
+        self.groups = groups
+            .into_iter()
+            .map(|(key, indices)| Group { key, indices })
+            .collect();
+    }
+
+    fn groups(&self) -> &dyn Any {
+        &self.groups
+    }
+}
+
+pub(crate) struct Group<K> {
+    key: K,
+    /// Indices into the data, in the same order as the visible values
+    indices: Vec<usize>,
+}
+
+/// All groups of a grouping registered with
+/// [ImplData::group_by](super::ImplData::group_by), ordered by their key.
+/// Only values passing the filters are grouped and within a group values
+/// keep the current sorting.
+pub struct GroupsView<'a, K, Value> {
+    groups: &'a [Group<K>],
+    data: &'a [Value],
+}
+
+impl<'a, K, Value> GroupsView<'a, K, Value>
+where
+    K: Ord,
+{
+    pub(crate) fn new(groups: &'a [Group<K>], data: &'a [Value]) -> Self {
+        Self { groups, data }
+    }
+
+    /// Number of groups
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = GroupView<'a, K, Value>> {
+        let data = self.data;
+        self.groups
+            .iter()
+            .map(move |group| GroupView { group, data })
+    }
+
+    pub fn get(&self, key: &K) -> Option<GroupView<'a, K, Value>> {
+        let position = self
+            .groups
+            .binary_search_by(|group| group.key.cmp(key))
+            .ok()?;
+        Some(GroupView {
+            group: &self.groups[position],
+            data: self.data,
+        })
+    }
+
+    /// Every key together with the amount of values in its group
+    pub fn counts(&self) -> impl ExactSizeIterator<Item = (&'a K, usize)> {
+        self.groups
+            .iter()
+            .map(|group| (&group.key, group.indices.len()))
+    }
+}
+
+/// A single group of a [GroupsView]
+pub struct GroupView<'a, K, Value> {
+    group: &'a Group<K>,
+    data: &'a [Value],
+}
+
+impl<'a, K, Value> GroupView<'a, K, Value> {
+    pub fn key(&self) -> &'a K {
+        &self.group.key
+    }
+
+    /// Number of values in this group
+    pub fn len(&self) -> usize {
+        self.group.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.group.indices.is_empty()
+    }
+
+    pub fn values(&self) -> impl ExactSizeIterator<Item = &'a Value> {
+        let data = self.data;
+        self.group.indices.iter().map(move |index| &data[*index])
+    }
+}
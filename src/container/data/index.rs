@@ -0,0 +1,105 @@
+use std::{any::Any, collections::HashMap, hash::Hash};
+
+type KeyFn<Value, K> = Box<dyn Fn(&Value) -> K + Sync + Send + 'static>;
+
+/// Type erased index so that indexes with different key types can be stored
+/// next to each other.
+pub(crate) trait AnyIndex<Value>: Sync + Send {
+    fn rebuild(&mut self, data: &[Value]);
+    /// Adds the rows of `data` starting at `from`, which were appended
+    fn extend(&mut self, data: &[Value], from: usize);
+    fn entries(&self) -> &dyn Any;
+}
+
+pub(crate) struct Index<K, Value> {
+    key_fn: KeyFn<Value, K>,
+    entries: HashMap<K, Vec<usize>>,
+}
+
+impl<K, Value> Index<K, Value> {
+    pub fn new(key_fn: impl Fn(&Value) -> K + Sync + Send + 'static) -> Self {
+        Self {
+            key_fn: Box::new(key_fn),
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl<K, Value> AnyIndex<Value> for Index<K, Value>
+where
+    K: Hash + Eq + Sync + Send + 'static,
+{
+    fn rebuild(&mut self, data: &[Value]) {
+        self.entries.clear();
+        self.extend(data, 0);
+    }
+
+    fn extend(&mut self, data: &[Value], from: usize) {
+        for (index, value) in data.iter().enumerate().skip(from) {
+            self.entries
+                .entry((self.key_fn)(value))
+                .or_default()
+                .push(index);
+        }
+    }
+
+    fn entries(&self) -> &dyn Any {
+        &self.entries
+    }
+}
+
+/// Read only view of an index registered with
+/// [ImplData::index_by](super::ImplData::index_by). Lookups are done in
+/// constant time and return the values in the order of
+/// [ImplData::data](super::ImplData::data), ignoring sorting and filters.
+pub struct IndexView<'a, K, Value> {
+    entries: &'a HashMap<K, Vec<usize>>,
+    data: &'a [Value],
+}
+
+impl<'a, K, Value> IndexView<'a, K, Value>
+where
+    K: Hash + Eq,
+{
+    pub(crate) fn new(entries: &'a HashMap<K, Vec<usize>>, data: &'a [Value]) -> Self {
+        Self { entries, data }
+    }
+
+    /// All values with the passed key, empty if there are none
+    pub fn get(&self, key: &K) -> impl ExactSizeIterator<Item = &'a Value> {
+        let data = self.data;
+        self.entries
+            .get(key)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .map(move |index| &data[*index])
+    }
+
+    /// The first value with the passed key, useful for unique keys
+    pub fn first(&self, key: &K) -> Option<&'a Value> {
+        self.get(key).next()
+    }
+
+    pub fn count(&self, key: &K) -> usize {
+        self.entries.get(key).map(Vec::len).unwrap_or_default()
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// All distinct keys, in no particular order
+    pub fn keys(&self) -> impl ExactSizeIterator<Item = &'a K> {
+        self.entries.keys()
+    }
+
+    /// Number of distinct keys
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+/// The values of a [Data](super::Data) at one generation
+pub(crate) struct Snapshot<Value> {
+    generation: u64,
+    data: Arc<[Value]>,
+}
+
+impl<Value> Snapshot<Value> {
+    pub fn new(generation: u64, data: Arc<[Value]>) -> Self {
+        Self { generation, data }
+    }
+}
+
+/// Follows the values of a [Data](super::Data) from somewhere else, for
+/// example another container, without borrowing it. Created through
+/// [ImplData::observe](super::ImplData::observe).
+pub struct DataObserver<Value> {
+    reciver: watch::Receiver<Snapshot<Value>>,
+    seen_generation: Option<u64>,
+}
+
+impl<Value> DataObserver<Value> {
+    pub(crate) fn new(reciver: watch::Receiver<Snapshot<Value>>) -> Self {
+        Self {
+            reciver,
+            seen_generation: None,
+        }
+    }
+
+    /// The [generation](super::Data::generation) of the observed data
+    pub fn generation(&self) -> u64 {
+        self.reciver.borrow().generation
+    }
+
+    /// The observed values in their original order
+    pub fn data(&self) -> Arc<[Value]> {
+        self.reciver.borrow().data.clone()
+    }
+
+    /// Returns if the data changed since the last call, the first call always
+    /// returns `true`
+    pub fn changed(&mut self) -> bool {
+        let generation = self.generation();
+        let changed = self.seen_generation != Some(generation);
+        self.seen_generation = Some(generation);
+        changed
+    }
+}
+
+impl<Value> Clone for DataObserver<Value> {
+    fn clone(&self) -> Self {
+        Self {
+            reciver: self.reciver.clone(),
+            seen_generation: None,
+        }
+    }
+}
@@ -0,0 +1,60 @@
+/// A set of row level changes applied to [Data](super::Data) at once through
+/// [Data::patch](super::Data::patch).
+///
+/// All indices refer to the positions in [Data::data](super::ImplData::data)
+/// before the patch is applied. Updates are applied first, then removals and
+/// lastly the new values are appended at the end.
+pub struct DataPatch<Value> {
+    pub(super) updates: Vec<(usize, Value)>,
+    pub(super) removals: Vec<usize>,
+    pub(super) inserts: Vec<Value>,
+}
+
+impl<Value> Default for DataPatch<Value> {
+    fn default() -> Self {
+        Self {
+            updates: vec![],
+            removals: vec![],
+            inserts: vec![],
+        }
+    }
+}
+
+impl<Value> DataPatch<Value> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the value at `index`
+    pub fn update(mut self, index: usize, value: Value) -> Self {
+        self.updates.push((index, value));
+        self
+    }
+
+    /// Removes the value at `index`
+    pub fn remove(mut self, index: usize) -> Self {
+        self.removals.push(index);
+        self
+    }
+
+    /// Appends a new value
+    pub fn insert(mut self, value: Value) -> Self {
+        self.inserts.push(value);
+        self
+    }
+
+    /// Appends all new values
+    pub fn insert_many(mut self, values: impl IntoIterator<Item = Value>) -> Self {
+        self.inserts.extend(values);
+        self
+    }
+
+    /// Number of rows touched by this patch
+    pub fn len(&self) -> usize {
+        self.updates.len() + self.removals.len() + self.inserts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
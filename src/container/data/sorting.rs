@@ -0,0 +1,190 @@
+use std::cmp::Ordering;
+
+pub(crate) type SortingFn<Value> = Box<dyn Fn(&Value, &Value) -> Ordering + Sync + Send + 'static>;
+
+/// Direction in which a single [SortKey] orders the values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortDirection {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+impl SortDirection {
+    /// Returns the opposite direction
+    pub fn toggled(self) -> Self {
+        match self {
+            Self::Ascending => Self::Descending,
+            Self::Descending => Self::Ascending,
+        }
+    }
+
+    fn apply(self, ordering: Ordering) -> Ordering {
+        match self {
+            Self::Ascending => ordering,
+            Self::Descending => ordering.reverse(),
+        }
+    }
+}
+
+/// A single named key of a [SortSpec]. The name is used to find the key again
+/// later on, for example to toggle its direction when a column header is clicked.
+pub struct SortKey<Value> {
+    name: String,
+    direction: SortDirection,
+    compare: SortingFn<Value>,
+}
+
+impl<Value> SortKey<Value> {
+    /// Name of the key registered by [ImplData::sort](super::ImplData::sort)
+    pub const DEFAULT_NAME: &'static str = "default";
+
+    /// Creates an ascending key from a function extracting the value to sort by
+    pub fn by_key<K>(
+        name: impl Into<String>,
+        key_fn: impl Fn(&Value) -> K + Send + Sync + 'static,
+    ) -> Self
+    where
+        K: Ord,
+    {
+        Self::by(name, move |a, b| key_fn(a).cmp(&key_fn(b)))
+    }
+
+    /// Creates an ascending key from a comparator
+    pub fn by(
+        name: impl Into<String>,
+        compare: impl Fn(&Value, &Value) -> Ordering + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            direction: SortDirection::Ascending,
+            compare: Box::new(compare),
+        }
+    }
+
+    /// Sets the direction of this key
+    pub fn direction(mut self, direction: SortDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// Shorthand for `direction(SortDirection::Descending)`
+    pub fn descending(self) -> Self {
+        self.direction(SortDirection::Descending)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn current_direction(&self) -> SortDirection {
+        self.direction
+    }
+
+    fn compare(&self, a: &Value, b: &Value) -> Ordering {
+        self.direction.apply((self.compare)(a, b))
+    }
+}
+
+/// An ordered list of [SortKey]'s. Values are compared by the first key and
+/// every following key is only used to break ties of the previous ones.
+///
+/// ```rust
+/// use hermes::container::data::sorting::{SortKey, SortSpec};
+///
+/// struct Task { status: u8, due: u32 }
+///
+/// let spec = SortSpec::new()
+///     .then(SortKey::by_key("status", |t: &Task| t.status))
+///     .then(SortKey::by_key("due", |t: &Task| t.due).descending());
+/// assert_eq!(vec!["status", "due"], spec.names().collect::<Vec<_>>());
+/// ```
+pub struct SortSpec<Value> {
+    keys: Vec<SortKey<Value>>,
+}
+
+impl<Value> Default for SortSpec<Value> {
+    fn default() -> Self {
+        Self { keys: vec![] }
+    }
+}
+
+impl<Value> From<SortKey<Value>> for SortSpec<Value> {
+    fn from(key: SortKey<Value>) -> Self {
+        Self { keys: vec![key] }
+    }
+}
+
+impl<Value> SortSpec<Value> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builder version of [SortSpec::push]
+    pub fn then(mut self, key: SortKey<Value>) -> Self {
+        self.push(key);
+        self
+    }
+
+    /// Adds a key with the lowest priority. If a key with the same name is
+    /// already present it is replaced in place, keeping its priority.
+    pub fn push(&mut self, key: SortKey<Value>) {
+        match self.position(&key.name) {
+            Some(pos) => self.keys[pos] = key,
+            None => self.keys.push(key),
+        }
+    }
+
+    /// Removes all keys and only sorts by the passed one
+    pub fn replace(&mut self, key: SortKey<Value>) {
+        self.keys.clear();
+        self.keys.push(key);
+    }
+
+    /// Removes the key with the passed name, returning it if it was present
+    pub fn remove(&mut self, name: &str) -> Option<SortKey<Value>> {
+        self.position(name).map(|pos| self.keys.remove(pos))
+    }
+
+    /// Flips the direction of the key with the passed name. Returns the new
+    /// direction or `None` if there is no such key.
+    pub fn toggle(&mut self, name: &str) -> Option<SortDirection> {
+        let key = self.keys.iter_mut().find(|key| key.name == name)?;
+        key.direction = key.direction.toggled();
+        Some(key.direction)
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Direction of the key with the passed name, `None` if it is not part of
+    /// this spec
+    pub fn direction_of(&self, name: &str) -> Option<SortDirection> {
+        self.keys
+            .iter()
+            .find(|key| key.name == name)
+            .map(|key| key.direction)
+    }
+
+    /// Names of all keys in order of their priority
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.keys.iter().map(SortKey::name)
+    }
+
+    pub fn compare(&self, a: &Value, b: &Value) -> Ordering {
+        self.keys
+            .iter()
+            .map(|key| key.compare(a, b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.keys.iter().position(|key| key.name == name)
+    }
+}
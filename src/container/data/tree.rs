@@ -0,0 +1,250 @@
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+type KeyFn<Value, K> = Box<dyn Fn(&Value) -> K + Sync + Send + 'static>;
+
+/// Type erased tree, so that [Data](super::Data) doesn't need to know the
+/// key type.
+pub(crate) trait AnyTree<Value>: Sync + Send {
+    fn rebuild(&mut self, data: &[Value], visible: &[usize]);
+    fn nodes(&self) -> &dyn Any;
+    fn nodes_mut(&mut self) -> &mut dyn Any;
+    /// Expands every node that currently has children
+    fn expand_all(&mut self);
+    fn collapse_all(&mut self);
+}
+
+pub(crate) struct Tree<K, Value> {
+    key_fn: KeyFn<Value, K>,
+    parent_fn: KeyFn<Value, Option<K>>,
+    nodes: TreeNodes<K>,
+}
+
+impl<K, Value> Tree<K, Value> {
+    pub fn new(
+        key_fn: impl Fn(&Value) -> K + Sync + Send + 'static,
+        parent_fn: impl Fn(&Value) -> Option<K> + Sync + Send + 'static,
+    ) -> Self {
+        Self {
+            key_fn: Box::new(key_fn),
+            parent_fn: Box::new(parent_fn),
+            nodes: TreeNodes {
+                roots: vec![],
+                children: vec![],
+                keys: vec![],
+                positions: HashMap::new(),
+                expanded: HashSet::new(),
+            },
+        }
+    }
+}
+
+impl<K, Value> AnyTree<Value> for Tree<K, Value>
+where
+    K: Clone + Eq + Hash + Sync + Send + 'static,
+{
+    fn rebuild(&mut self, data: &[Value], visible: &[usize]) {
+        let nodes = &mut self.nodes;
+        nodes.keys = data.iter().map(&self.key_fn).collect();
+        nodes.positions = nodes
+            .keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| (key.clone(), index))
+            .collect();
+        nodes.roots.clear();
+        nodes.children = vec![vec![]; data.len()];
+
+        for index in visible {
+            let parent = (self.parent_fn)(&data[*index])
+                .and_then(|parent| nodes.positions.get(&parent).copied())
+                .filter(|parent| parent != index);
+            match parent {
+                Some(parent) => nodes.children[parent].push(*index),
+                None => nodes.roots.push(*index),
+            }
+        }
+    }
+
+    fn nodes(&self) -> &dyn Any {
+        &self.nodes
+    }
+
+    fn nodes_mut(&mut self) -> &mut dyn Any {
+        &mut self.nodes
+    }
+
+    fn expand_all(&mut self) {
+        let nodes = &mut self.nodes;
+        let parents = nodes
+            .children
+            .iter()
+            .enumerate()
+            .filter(|(_, children)| !children.is_empty())
+            .map(|(index, _)| nodes.keys[index].clone())
+            .collect::<Vec<_>>();
+        nodes.expanded.extend(parents);
+    }
+
+    fn collapse_all(&mut self) {
+        self.nodes.expanded.clear();
+    }
+}
+
+/// The structure of a tree, all indices point into the data
+pub(crate) struct TreeNodes<K> {
+    /// Values without a parent in the data, in sorted order
+    roots: Vec<usize>,
+    /// Children of every value, in sorted order
+    children: Vec<Vec<usize>>,
+    keys: Vec<K>,
+    positions: HashMap<K, usize>,
+    /// Kept by key so that it survives refreshes
+    expanded: HashSet<K>,
+}
+
+impl<K> TreeNodes<K>
+where
+    K: Clone + Eq + Hash,
+{
+    pub fn expand(&mut self, key: K) {
+        self.expanded.insert(key);
+    }
+
+    pub fn collapse(&mut self, key: &K) {
+        self.expanded.remove(key);
+    }
+
+    /// Returns if the node is expanded afterwards
+    pub fn toggle(&mut self, key: K) -> bool {
+        if self.expanded.remove(&key) {
+            return false;
+        }
+        self.expanded.insert(key);
+        true
+    }
+}
+
+/// The tree registered with [ImplData::tree_by](super::ImplData::tree_by).
+/// Values whose parent is not in the data are roots, values hidden by a
+/// filter hide their whole subtree. Siblings keep the current sorting.
+pub struct TreeView<'a, K, Value> {
+    nodes: &'a TreeNodes<K>,
+    data: &'a [Value],
+}
+
+impl<'a, K, Value> TreeView<'a, K, Value>
+where
+    K: Eq + Hash,
+{
+    pub(crate) fn new(nodes: &'a TreeNodes<K>, data: &'a [Value]) -> Self {
+        Self { nodes, data }
+    }
+
+    pub fn roots(&self) -> impl ExactSizeIterator<Item = NodeView<'a, K, Value>> {
+        let nodes = self.nodes;
+        let data = self.data;
+        nodes.roots.iter().map(move |index| NodeView {
+            index: *index,
+            nodes,
+            data,
+        })
+    }
+
+    pub fn get(&self, key: &K) -> Option<NodeView<'a, K, Value>> {
+        self.nodes.positions.get(key).map(|index| self.view(*index))
+    }
+
+    pub fn is_expanded(&self, key: &K) -> bool {
+        self.nodes.expanded.contains(key)
+    }
+
+    /// All nodes that are shown, meaning the roots and the children of
+    /// expanded nodes, depth first in the order they would be listed
+    pub fn flatten(&self) -> Vec<FlatNode<'a, K, Value>> {
+        let mut flat = vec![];
+        let mut stack = self
+            .nodes
+            .roots
+            .iter()
+            .rev()
+            .map(|index| (*index, 0))
+            .collect::<Vec<_>>();
+        while let Some((index, depth)) = stack.pop() {
+            let node = self.view(index);
+            let expanded = node.is_expanded();
+            if expanded {
+                stack.extend(
+                    self.nodes.children[index]
+                        .iter()
+                        .rev()
+                        .map(|child| (*child, depth + 1)),
+                );
+            }
+            flat.push(FlatNode {
+                node,
+                depth,
+                expanded,
+            });
+        }
+        flat
+    }
+
+    fn view(&self, index: usize) -> NodeView<'a, K, Value> {
+        NodeView {
+            index,
+            nodes: self.nodes,
+            data: self.data,
+        }
+    }
+}
+
+/// A single value of a [TreeView]
+pub struct NodeView<'a, K, Value> {
+    index: usize,
+    nodes: &'a TreeNodes<K>,
+    data: &'a [Value],
+}
+
+impl<'a, K, Value> NodeView<'a, K, Value>
+where
+    K: Eq + Hash,
+{
+    pub fn key(&self) -> &'a K {
+        &self.nodes.keys[self.index]
+    }
+
+    pub fn value(&self) -> &'a Value {
+        &self.data[self.index]
+    }
+
+    pub fn has_children(&self) -> bool {
+        !self.nodes.children[self.index].is_empty()
+    }
+
+    pub fn is_expanded(&self) -> bool {
+        self.nodes.expanded.contains(self.key())
+    }
+
+    pub fn children(&self) -> impl ExactSizeIterator<Item = NodeView<'a, K, Value>> {
+        let nodes = self.nodes;
+        let data = self.data;
+        nodes.children[self.index]
+            .iter()
+            .map(move |index| NodeView {
+                index: *index,
+                nodes,
+                data,
+            })
+    }
+}
+
+/// A node of [TreeView::flatten] together with its position in the tree
+pub struct FlatNode<'a, K, Value> {
+    pub node: NodeView<'a, K, Value>,
+    pub depth: usize,
+    pub expanded: bool,
+}
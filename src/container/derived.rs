@@ -0,0 +1,141 @@
+use super::data::{observer::DataObserver, Data, HasData};
+
+type Compute<Value> = Box<dyn FnMut() -> Option<Vec<Value>> + Send + 'static>;
+
+/// Container whose values are computed from the data of other containers,
+/// for example totals per category. The sources are followed through
+/// [DataObserver]s and the values are only recomputed when the generation of
+/// one of them changed.
+pub struct DerivedContainer<Value> {
+    pub data: Data<Value>,
+    compute: Compute<Value>,
+}
+
+impl<Value> DerivedContainer<Value>
+where
+    Value: Send + 'static,
+{
+    /// Derives the values from a single source
+    pub fn new<A>(
+        mut source: DataObserver<A>,
+        derive: impl Fn(&[A]) -> Vec<Value> + Send + 'static,
+    ) -> Self
+    where
+        A: Send + Sync + 'static,
+    {
+        Self::from_compute(Box::new(move || {
+            source.changed().then(|| derive(&source.data()))
+        }))
+    }
+
+    /// Derives the values from two sources, recomputing when either changes
+    pub fn from_two<A, B>(
+        mut first: DataObserver<A>,
+        mut second: DataObserver<B>,
+        derive: impl Fn(&[A], &[B]) -> Vec<Value> + Send + 'static,
+    ) -> Self
+    where
+        A: Send + Sync + 'static,
+        B: Send + Sync + 'static,
+    {
+        Self::from_compute(Box::new(move || {
+            // both have to be called to mark their generation as seen
+            let first_changed = first.changed();
+            let second_changed = second.changed();
+            (first_changed || second_changed).then(|| derive(&first.data(), &second.data()))
+        }))
+    }
+
+    fn from_compute(compute: Compute<Value>) -> Self {
+        Self {
+            data: Data::default(),
+            compute,
+        }
+    }
+
+    /// Recomputes the values if any source changed since the last update
+    pub fn state_update(&mut self) {
+        if let Some(values) = (self.compute)() {
+            self.data.set(values.into_iter());
+        }
+    }
+}
+
+impl<Value> HasData<Value> for DerivedContainer<Value> {
+    fn ref_data(&self) -> &Data<Value> {
+        &self.data
+    }
+
+    fn ref_mut_data(&mut self) -> &mut Data<Value> {
+        &mut self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::container::data::{patch::DataPatch, ImplData};
+
+    #[test]
+    fn recomputes_only_when_a_source_changes() {
+        // (category, amount)
+        let mut expenses = Data::from(vec![(1, 10), (2, 5), (1, 7)]);
+        let mut derived = DerivedContainer::new(expenses.observe(), |expenses: &[(u8, u32)]| {
+            let mut totals = BTreeMap::<u8, u32>::new();
+            for (category, amount) in expenses {
+                *totals.entry(*category).or_default() += amount;
+            }
+            totals.into_iter().collect()
+        });
+        derived.sort(|a, b| b.1.cmp(&a.1));
+
+        derived.state_update();
+        assert_eq!(vec![&(1, 17), &(2, 5)], derived.sorted());
+        let generation = derived.generation();
+
+        derived.state_update();
+        assert_eq!(generation, derived.generation());
+
+        expenses.patch(DataPatch::new().insert((2, 20)));
+        derived.state_update();
+        assert_eq!(vec![&(2, 25), &(1, 17)], derived.sorted());
+        assert_eq!(generation + 1, derived.generation());
+    }
+
+    #[test]
+    fn two_sources() {
+        let mut names = Data::from(vec![(1, "food"), (2, "rent")]);
+        let mut limits = Data::from(vec![(1, 100)]);
+        let mut derived = DerivedContainer::from_two(
+            names.observe(),
+            limits.observe(),
+            |names: &[(u8, &'static str)], limits: &[(u8, u32)]| {
+                names
+                    .iter()
+                    .map(|(id, name)| {
+                        let limit = limits.iter().find(|(other, _)| other == id);
+                        (*name, limit.map(|(_, limit)| *limit))
+                    })
+                    .collect()
+            },
+        );
+
+        derived.state_update();
+        assert_eq!(
+            vec![("food", Some(100)), ("rent", None)],
+            derived.data().to_vec()
+        );
+
+        limits.set(vec![(2, 900)].into_iter());
+        derived.state_update();
+        assert_eq!(
+            vec![("food", None), ("rent", Some(900))],
+            derived.data().to_vec()
+        );
+        names.set(vec![].into_iter());
+        derived.state_update();
+        assert!(derived.data().is_empty());
+    }
+}
@@ -0,0 +1,54 @@
+use chrono::{DateTime, FixedOffset};
+use tokio::{sync::mpsc, task};
+use tracing::warn;
+
+use crate::{consts::quote_table, messenger::ContainerData};
+
+/// Connection of a container to the [Messenger](crate::messenger::Messenger)
+/// for a fixed set of tables, telling it whenever one of them changed. Used by
+/// containers that don't query through a carrier or need to know which of
+/// their queries is affected.
+pub(crate) struct Invalidation {
+    time_of_change_reciver: mpsc::Receiver<DateTime<FixedOffset>>,
+}
+
+impl Invalidation {
+    pub fn register(
+        name: &str,
+        all_tables: &[String],
+        tables: &[&str],
+        new_register_sender: mpsc::Sender<ContainerData>,
+    ) -> Self {
+        let tables = tables
+            .iter()
+            .map(|table| quote_table(table))
+            .inspect(|table| {
+                if !all_tables.contains(table) {
+                    warn!("{name} depends on the unknown table {table}");
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let (tables_interested_sender, tables_interested_reciever) = mpsc::channel(1);
+        let (update_sender, update_reciver) = mpsc::channel(10);
+        let _ = tables_interested_sender.try_send(tables);
+
+        let data = ContainerData::new(tables_interested_reciever, update_sender);
+        task::spawn(async move {
+            let _ = new_register_sender.send(data).await;
+        });
+
+        Self {
+            time_of_change_reciver: update_reciver,
+        }
+    }
+
+    /// Returns if any of the tables changed since the last call
+    pub fn try_recive_changed(&mut self) -> bool {
+        let mut changed = false;
+        while self.time_of_change_reciver.try_recv().is_ok() {
+            changed = true;
+        }
+        changed
+    }
+}
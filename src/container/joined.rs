@@ -0,0 +1,121 @@
+use sea_orm::EntityTrait;
+use tracing::error;
+
+use crate::{
+    carrier::{
+        execute::{ExecuteCarrier, HasExecuteCarrier},
+        joined_query::{HasJoinedQueryCarrier, ImplJoinedQueryCarrier, Joined, JoinedQueryCarrier},
+        query::ImplQueryCarrier,
+    },
+    container::builder::ContainerBuilder,
+};
+
+use super::data::{Data, HasData};
+
+/// Container for rows of `A` together with their related row of `B`, queried
+/// through `find_also_related` or `find_with_related`. Since the query reads
+/// from both tables, changes to either of them refresh the container.
+pub struct JoinedContainer<A, B>
+where
+    A: EntityTrait + Send + 'static,
+    B: EntityTrait + Send + 'static,
+{
+    pub name: String,
+    pub data: Data<Joined<A, B>>,
+    query_carrier: JoinedQueryCarrier<A, B>,
+    execute_carrier: ExecuteCarrier,
+}
+
+impl<A, B> JoinedContainer<A, B>
+where
+    A: EntityTrait + Send + 'static,
+    B: EntityTrait + Send + 'static,
+    A::Model: Sync,
+    B::Model: Sync,
+{
+    pub(crate) fn from_carriers(
+        name: String,
+        query_carrier: JoinedQueryCarrier<A, B>,
+        execute_carrier: ExecuteCarrier,
+    ) -> Self {
+        Self {
+            name,
+            data: Data::default(),
+            query_carrier,
+            execute_carrier,
+        }
+    }
+
+    pub fn builder(&self) -> ContainerBuilder {
+        self.query_carrier.builder()
+    }
+
+    pub fn state_update(&mut self, automatic_requery: bool) {
+        self.query_carrier.try_recive_should_update();
+        if let Some(result) = self.query_carrier.try_resolve_query() {
+            match result {
+                Ok(values) => self.data.set_shared(values),
+                Err(error) => error!(container = self.name, error = error.to_string()),
+            }
+        }
+        self.execute_carrier.try_resolve_executes();
+
+        if automatic_requery && self.should_refresh() {
+            self.query_carrier.rerun_stored_query();
+        }
+    }
+}
+
+impl<A, B> HasJoinedQueryCarrier<A, B> for JoinedContainer<A, B>
+where
+    A: EntityTrait + Send + 'static,
+    B: EntityTrait + Send + 'static,
+{
+    fn ref_joined_query_carrier(&self) -> &JoinedQueryCarrier<A, B> {
+        &self.query_carrier
+    }
+    fn ref_mut_joined_query_carrier(&mut self) -> &mut JoinedQueryCarrier<A, B> {
+        &mut self.query_carrier
+    }
+}
+
+impl<A, B> HasExecuteCarrier for JoinedContainer<A, B>
+where
+    A: EntityTrait + Send + 'static,
+    B: EntityTrait + Send + 'static,
+{
+    fn ref_execute_carrier(&self) -> &ExecuteCarrier {
+        &self.execute_carrier
+    }
+    fn ref_mut_execute_carrier(&mut self) -> &mut ExecuteCarrier {
+        &mut self.execute_carrier
+    }
+}
+
+impl<A, B> HasData<Joined<A, B>> for JoinedContainer<A, B>
+where
+    A: EntityTrait + Send + 'static,
+    B: EntityTrait + Send + 'static,
+{
+    fn ref_data(&self) -> &Data<Joined<A, B>> {
+        &self.data
+    }
+    fn ref_mut_data(&mut self) -> &mut Data<Joined<A, B>> {
+        &mut self.data
+    }
+}
+
+impl<A, B> Clone for JoinedContainer<A, B>
+where
+    A: EntityTrait + Send + 'static,
+    B: EntityTrait + Send + 'static,
+{
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            data: Data::default(),
+            query_carrier: self.query_carrier.clone(),
+            execute_carrier: self.execute_carrier.clone(),
+        }
+    }
+}
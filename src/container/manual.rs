@@ -54,7 +54,7 @@ where
         self.query_carrier.try_recive_should_update();
         if let Some(result) = self.query_carrier.try_resolve_query() {
             match result {
-                Ok(values) => self.data.set(values.into_iter()),
+                Ok(values) => self.data.set_shared(values),
                 Err(error) => error!(container = self.name, error = error.to_string()),
             }
         }
@@ -0,0 +1,355 @@
+pub mod level;
+
+use std::{mem, sync::Arc};
+
+use sea_orm::{DatabaseConnection, DbErr, EntityTrait, QueryTrait, Select};
+use tokio::sync::mpsc;
+use tracing::error;
+
+use crate::{
+    carrier::{
+        execute::{ExecuteCarrier, HasExecuteCarrier},
+        manual_query::{HasManualQueryCarrier, ImplManualQueryCarrier, ManualQueryCarrier},
+        query::{ExecutedQuery, ImplQueryCarrier},
+    },
+    container::{builder::ContainerBuilder, invalidation::Invalidation},
+    messenger::ContainerData,
+    TablesCollector, QUERY_BUILDER,
+};
+
+use super::data::{Data, HasData};
+use level::{Cached, NestedLevel};
+
+type Projection<Parent, Children, Value> = dyn Fn(Parent, Children) -> Value + Send + Sync;
+
+/// Container for parents of type `P` together with all their children, for
+/// example a project with its tasks and their subtasks.
+///
+/// The parents are queried with a stored [Select], the children are then
+/// loaded in one batch per [level](level::NestedLevel) and assembled into
+/// `Value`s by a projection. When the table of a level changes only that
+/// level and the ones below it are loaded again.
+pub struct NestedContainer<Value, P, Children>
+where
+    Value: Send + 'static,
+    P: EntityTrait + Send + 'static,
+    Children: Send + 'static,
+{
+    pub name: String,
+    pub data: Data<Value>,
+    query_carrier: ManualQueryCarrier<Value>,
+    execute_carrier: ExecuteCarrier,
+
+    all_tables: Vec<String>,
+    new_register_sender: mpsc::Sender<ContainerData>,
+    loader: Option<Arc<NestedLoader<Value, P, Children>>>,
+    /// The table of every level of children with its invalidation, ordered
+    /// by depth
+    levels: Vec<(String, Invalidation)>,
+    /// Depth of the highest level that changed but was not loaded again yet,
+    /// the parents being depth `0`
+    pending_reload: Option<usize>,
+    /// Depth the running load started from, if one is running
+    loading: Option<usize>,
+    /// Counts up with every load, so that older loads still running can't
+    /// replace what newer ones cached
+    generation: u64,
+}
+
+struct NestedLoader<Value, P, Children>
+where
+    P: EntityTrait,
+{
+    select: Select<P>,
+    sql: String,
+    parents: Cached<Vec<P::Model>>,
+    children: Box<dyn NestedLevel<P::Model, Output = Children>>,
+    projection: Box<Projection<P::Model, Children, Value>>,
+}
+
+impl<Value, P, Children> NestedLoader<Value, P, Children>
+where
+    P: EntityTrait,
+    P::Model: Sync,
+    Children: Clone + Send + 'static,
+{
+    async fn load(
+        &self,
+        db: &DatabaseConnection,
+        collector: &mut TablesCollector,
+        reload_from: usize,
+        generation: u64,
+    ) -> Result<Vec<Value>, DbErr> {
+        collector.add(&self.sql);
+        let cached = match reload_from {
+            0 => None,
+            _ => self.parents.get(),
+        };
+        let (parents, reload_from) = match cached {
+            Some(parents) => (parents, reload_from - 1),
+            None => {
+                let parents = self.select.clone().all(db).await?;
+                self.parents.store(generation, &parents);
+                (parents, 0)
+            }
+        };
+
+        let children = self
+            .children
+            .load(db, &parents, reload_from, generation)
+            .await?;
+        Ok(parents
+            .into_iter()
+            .zip(children)
+            .map(|(parent, children)| (self.projection)(parent, children))
+            .collect())
+    }
+}
+
+impl<Value, P, Children> NestedContainer<Value, P, Children>
+where
+    Value: Send + Sync + 'static,
+    P: EntityTrait + Send + Sync + 'static,
+    P::Model: Sync,
+    Children: Clone + Send + 'static,
+{
+    pub(crate) fn from_carriers(
+        name: String,
+        query_carrier: ManualQueryCarrier<Value>,
+        execute_carrier: ExecuteCarrier,
+        all_tables: Vec<String>,
+        new_register_sender: mpsc::Sender<ContainerData>,
+    ) -> Self {
+        Self {
+            name,
+            data: Data::default(),
+            query_carrier,
+            execute_carrier,
+            all_tables,
+            new_register_sender,
+            loader: None,
+            levels: vec![],
+            pending_reload: None,
+            loading: None,
+            generation: 0,
+        }
+    }
+
+    pub fn builder(&self) -> ContainerBuilder {
+        self.query_carrier.builder()
+    }
+
+    /// Queries the parents with `select` and their children with `children`,
+    /// building the values with `projection`. Everything is stored to be
+    /// loaded again whenever one of the involved tables changes.
+    ///
+    /// Calling this again replaces the stored query. Levels whose table stays
+    /// the same keep their registration with the
+    /// [Messenger](crate::messenger::Messenger), the others are dropped and
+    /// unregistered by it.
+    pub fn stored_query<L>(
+        &mut self,
+        mut select: Select<P>,
+        children: L,
+        projection: impl Fn(P::Model, Children) -> Value + Send + Sync + 'static,
+    ) where
+        L: NestedLevel<P::Model, Output = Children>,
+    {
+        let mut previous = mem::take(&mut self.levels);
+        self.levels = children
+            .tables()
+            .into_iter()
+            .map(
+                |table| match previous.iter().position(|(known, _)| *known == table) {
+                    Some(index) => previous.swap_remove(index),
+                    None => {
+                        let invalidation = Invalidation::register(
+                            &self.name,
+                            &self.all_tables,
+                            &[table.as_str()],
+                            self.new_register_sender.clone(),
+                        );
+                        (table, invalidation)
+                    }
+                },
+            )
+            .collect();
+        let _ = self.loader.insert(Arc::new(NestedLoader {
+            sql: select.query().to_string(QUERY_BUILDER),
+            select,
+            parents: Cached::default(),
+            children: Box::new(children),
+            projection: Box::new(projection),
+        }));
+        self.loading = None;
+        self.load(0);
+    }
+
+    /// Loads from the depth `reload_from` on. A load that is still running is
+    /// replaced, so the new one also reloads everything the old one would
+    /// have.
+    fn load(&mut self, reload_from: usize) {
+        let Some(loader) = self.loader.clone() else {
+            return;
+        };
+        let reload_from = self
+            .loading
+            .map_or(reload_from, |loading| loading.min(reload_from));
+        self.pending_reload = None;
+        self.loading = Some(reload_from);
+        self.generation += 1;
+        let generation = self.generation;
+        self.query_carrier
+            .manual_query(move |db, mut collector| async move {
+                let result = loader
+                    .load(&db, &mut collector, reload_from, generation)
+                    .await;
+                ExecutedQuery::new_collector(collector, result)
+            });
+    }
+
+    pub fn state_update(&mut self, automatic_requery: bool) {
+        self.query_carrier.try_recive_should_update();
+        if let Some(result) = self.query_carrier.try_resolve_query() {
+            self.loading = None;
+            match result {
+                Ok(values) => self.data.set_shared(values),
+                Err(error) => error!(container = self.name, error = error.to_string()),
+            }
+        }
+        self.execute_carrier.try_resolve_executes();
+
+        for (index, (_, level)) in self.levels.iter_mut().enumerate() {
+            if level.try_recive_changed() {
+                let depth = index + 1;
+                self.pending_reload = Some(self.pending_reload.map_or(depth, |d| d.min(depth)));
+            }
+        }
+
+        if automatic_requery {
+            if self.should_refresh() {
+                self.load(0);
+            } else if let Some(depth) = self.pending_reload {
+                self.load(depth);
+            }
+        }
+    }
+}
+
+impl<Value, P, Children> HasManualQueryCarrier<Value> for NestedContainer<Value, P, Children>
+where
+    Value: Send + 'static,
+    P: EntityTrait + Send + 'static,
+    Children: Send + 'static,
+{
+    fn ref_manual_query_carrier(&self) -> &ManualQueryCarrier<Value> {
+        &self.query_carrier
+    }
+
+    fn ref_mut_manual_query_carrier(&mut self) -> &mut ManualQueryCarrier<Value> {
+        &mut self.query_carrier
+    }
+}
+
+impl<Value, P, Children> HasExecuteCarrier for NestedContainer<Value, P, Children>
+where
+    Value: Send + 'static,
+    P: EntityTrait + Send + 'static,
+    Children: Send + 'static,
+{
+    fn ref_execute_carrier(&self) -> &ExecuteCarrier {
+        &self.execute_carrier
+    }
+
+    fn ref_mut_execute_carrier(&mut self) -> &mut ExecuteCarrier {
+        &mut self.execute_carrier
+    }
+}
+
+impl<Value, P, Children> HasData<Value> for NestedContainer<Value, P, Children>
+where
+    Value: Send + 'static,
+    P: EntityTrait + Send + 'static,
+    Children: Send + 'static,
+{
+    fn ref_data(&self) -> &Data<Value> {
+        &self.data
+    }
+
+    fn ref_mut_data(&mut self) -> &mut Data<Value> {
+        &mut self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use sea_orm::EntityTrait;
+    use tokio::{sync::mpsc, task::yield_now};
+
+    use super::{level::Children, NestedContainer};
+    use crate::{
+        carrier::shared_query::SharedQueries,
+        container::builder::ContainerBuilder,
+        messenger::ContainerData,
+        test_db::{self, all_tables, project, task, FakeDatabase},
+    };
+
+    type Projects =
+        NestedContainer<(project::Model, Vec<task::Model>), project::Entity, Vec<task::Model>>;
+
+    fn container() -> (Projects, mpsc::Receiver<ContainerData>) {
+        let fake = Arc::new(FakeDatabase::default());
+        let (tables_changed_sender, _) = mpsc::channel(10);
+        let (new_register_sender, new_register_reciver) = mpsc::channel(10);
+        let container = ContainerBuilder::new(
+            test_db::connect(&fake),
+            all_tables(),
+            tables_changed_sender,
+            new_register_sender,
+            SharedQueries::default(),
+        )
+        .nested();
+        (container, new_register_reciver)
+    }
+
+    fn store_query(container: &mut Projects) {
+        container.stored_query(
+            project::Entity::find(),
+            Children::<task::Entity>::all(),
+            |project, tasks| (project, tasks),
+        );
+    }
+
+    async fn registrations(reciver: &mut mpsc::Receiver<ContainerData>) -> usize {
+        yield_now().await;
+        let mut count = 0;
+        while reciver.try_recv().is_ok() {
+            count += 1;
+        }
+        count
+    }
+
+    #[tokio::test]
+    async fn stored_query_again_reuses_invalidations() {
+        let (mut container, mut reciver) = container();
+        store_query(&mut container);
+        assert!(registrations(&mut reciver).await > 0);
+
+        store_query(&mut container);
+        assert_eq!(registrations(&mut reciver).await, 0);
+        assert_eq!(container.levels.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn partial_reload_also_reloads_what_the_running_load_would() {
+        let (mut container, _reciver) = container();
+        store_query(&mut container);
+        assert_eq!(container.loading, Some(0));
+
+        container.load(1);
+        assert_eq!(container.loading, Some(0));
+        assert_eq!(container.generation, 2);
+    }
+}
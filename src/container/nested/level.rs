@@ -0,0 +1,244 @@
+use std::{future::Future, pin::Pin, sync::Mutex};
+
+use sea_orm::{DatabaseConnection, DbErr, EntityTrait, LoaderTrait, ModelTrait, Related, Select};
+
+pub type LevelFuture<'a, Output> =
+    Pin<Box<dyn Future<Output = Result<Vec<Output>, DbErr>> + Send + 'a>>;
+
+/// One level of children below the `Parent` models of a
+/// [NestedContainer](super::NestedContainer), loaded in one batch for all
+/// parents at once.
+///
+/// Levels keep the children they loaded last, so that a change further down
+/// the tree doesn't requery the levels above it.
+pub trait NestedLevel<Parent>: Send + Sync + 'static {
+    /// The children of a single parent
+    type Output: Clone + Send + 'static;
+
+    /// Loads the children of all `parents`, one entry per parent in the same
+    /// order. `reload_from` is the depth starting from this level which
+    /// changed, levels above it return what they loaded the last time.
+    /// `generation` counts up with every load of the container, what a load
+    /// queried is only kept if no newer one already stored its result.
+    fn load<'a>(
+        &'a self,
+        db: &'a DatabaseConnection,
+        parents: &'a [Parent],
+        reload_from: usize,
+        generation: u64,
+    ) -> LevelFuture<'a, Self::Output>;
+
+    /// The tables of this and all following levels, by depth
+    fn tables(&self) -> Vec<String>;
+}
+
+/// The children of type `C` of every parent
+pub struct Children<C>
+where
+    C: EntityTrait,
+{
+    select: Select<C>,
+    loaded: Cached<Vec<Vec<C::Model>>>,
+}
+
+impl<C> Children<C>
+where
+    C: EntityTrait,
+{
+    /// Loads the children through `select`, which can filter and order them
+    pub fn new(select: Select<C>) -> Self {
+        Self {
+            select,
+            loaded: Cached::default(),
+        }
+    }
+
+    pub fn all() -> Self {
+        Self::new(C::find())
+    }
+
+    /// Also loads the children of each child with `level`
+    pub fn with<L>(self, level: L) -> ChildrenWith<C, L>
+    where
+        L: NestedLevel<C::Model>,
+    {
+        ChildrenWith {
+            children: self,
+            level,
+        }
+    }
+
+    async fn load_children<Parent>(
+        &self,
+        db: &DatabaseConnection,
+        parents: &[Parent],
+        reload: bool,
+        generation: u64,
+    ) -> Result<Vec<Vec<C::Model>>, DbErr>
+    where
+        Parent: ModelTrait + Sync,
+        Parent::Entity: Related<C>,
+        C::Model: Sync,
+    {
+        if !reload {
+            if let Some(children) = self.loaded.get().filter(|c| c.len() == parents.len()) {
+                return Ok(children);
+            }
+        }
+        let children = parents.load_many(self.select.clone(), db).await?;
+        self.loaded.store(generation, &children);
+        Ok(children)
+    }
+
+    fn table() -> String {
+        C::default().table_name().to_owned()
+    }
+}
+
+impl<Parent, C> NestedLevel<Parent> for Children<C>
+where
+    Parent: ModelTrait + Sync,
+    Parent::Entity: Related<C>,
+    C: EntityTrait + Sync,
+    C::Model: Sync,
+{
+    type Output = Vec<C::Model>;
+
+    fn load<'a>(
+        &'a self,
+        db: &'a DatabaseConnection,
+        parents: &'a [Parent],
+        reload_from: usize,
+        generation: u64,
+    ) -> LevelFuture<'a, Self::Output> {
+        Box::pin(self.load_children(db, parents, reload_from == 0, generation))
+    }
+
+    fn tables(&self) -> Vec<String> {
+        vec![Self::table()]
+    }
+}
+
+/// The children of type `C` of every parent, each together with its own
+/// children loaded by `L`
+pub struct ChildrenWith<C, L>
+where
+    C: EntityTrait,
+{
+    children: Children<C>,
+    level: L,
+}
+
+impl<Parent, C, L> NestedLevel<Parent> for ChildrenWith<C, L>
+where
+    Parent: ModelTrait + Sync,
+    Parent::Entity: Related<C>,
+    C: EntityTrait + Sync,
+    C::Model: Sync,
+    L: NestedLevel<C::Model>,
+{
+    type Output = Vec<(C::Model, L::Output)>;
+
+    fn load<'a>(
+        &'a self,
+        db: &'a DatabaseConnection,
+        parents: &'a [Parent],
+        reload_from: usize,
+        generation: u64,
+    ) -> LevelFuture<'a, Self::Output> {
+        Box::pin(async move {
+            let reload = reload_from == 0;
+            let children = self
+                .children
+                .load_children(db, parents, reload, generation)
+                .await?;
+            let flat = children.iter().flatten().cloned().collect::<Vec<_>>();
+            let below = reload_from.saturating_sub(1);
+            let mut grandchildren = self
+                .level
+                .load(db, &flat, below, generation)
+                .await?
+                .into_iter();
+
+            Ok(children
+                .into_iter()
+                .map(|children| {
+                    children
+                        .into_iter()
+                        .zip(grandchildren.by_ref())
+                        .collect::<Vec<_>>()
+                })
+                .collect())
+        })
+    }
+
+    fn tables(&self) -> Vec<String> {
+        let mut tables = vec![Children::<C>::table()];
+        tables.extend(self.level.tables());
+        tables
+    }
+}
+
+/// What the newest load stored, older loads that finish later don't replace
+/// it
+pub(super) struct Cached<T>(Mutex<Option<(u64, T)>>);
+
+impl<T> Default for Cached<T> {
+    fn default() -> Self {
+        Self(Mutex::new(None))
+    }
+}
+
+impl<T> Cached<T>
+where
+    T: Clone,
+{
+    pub fn get(&self) -> Option<T> {
+        self.0
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|(_, value)| value.clone())
+    }
+
+    /// Stores `value` unless a load of a newer `generation` stored its value
+    /// already
+    pub fn store(&self, generation: u64, value: &T) {
+        let mut cached = self.0.lock().unwrap();
+        if cached
+            .as_ref()
+            .is_none_or(|(cached, _)| *cached < generation)
+        {
+            let _ = cached.insert((generation, value.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{Children, NestedLevel};
+    use crate::test_db::{self, project, row, task, FakeDatabase};
+
+    #[tokio::test]
+    async fn outdated_load_does_not_replace_newer_children() {
+        let fake = Arc::new(FakeDatabase::default());
+        let db = test_db::connect(&fake);
+        let children = Children::<task::Entity>::all();
+        let parents = [project::model(1)];
+        let newer = task::model(1, Some(1));
+        let outdated = task::model(2, Some(1));
+
+        fake.push_query(Ok(vec![row(&newer)]));
+        let loaded = children.load(&db, &parents, 0, 2).await.unwrap();
+        assert_eq!(loaded, vec![vec![newer.clone()]]);
+
+        fake.push_query(Ok(vec![row(&outdated)]));
+        let loaded = children.load(&db, &parents, 0, 1).await.unwrap();
+        assert_eq!(loaded, vec![vec![outdated]]);
+
+        let cached = children.load(&db, &parents, 1, 3).await.unwrap();
+        assert_eq!(cached, vec![vec![newer]]);
+    }
+}
@@ -0,0 +1,53 @@
+use chrono::Duration;
+use tokio::time::Instant;
+
+/// Keeps track of when a container should refresh on its own, for data
+/// sources that can't notify about changes.
+pub(crate) struct Polling {
+    interval: Option<std::time::Duration>,
+    paused: bool,
+    last_refresh: Instant,
+}
+
+impl Default for Polling {
+    fn default() -> Self {
+        Self {
+            interval: None,
+            paused: false,
+            last_refresh: Instant::now(),
+        }
+    }
+}
+
+impl Polling {
+    /// Sets the interval, `None` or a negative interval disables polling
+    pub fn set_interval(&mut self, interval: Option<Duration>) {
+        self.interval = interval.and_then(|interval| interval.to_std().ok());
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Returns if the interval has passed since the last refresh and polling
+    /// is not paused. Uses the tokio clock, so paused test time applies.
+    pub fn is_due(&self) -> bool {
+        !self.paused
+            && self
+                .interval
+                .is_some_and(|interval| self.last_refresh.elapsed() >= interval)
+    }
+
+    /// Starts the interval anew, to be called whenever the data is refreshed
+    pub fn restart(&mut self) {
+        self.last_refresh = Instant::now();
+    }
+}
@@ -1,19 +1,24 @@
-use std::{future::Future, pin::Pin};
+use std::{future::Future, pin::Pin, sync::Arc};
 
-use sea_orm::{DbErr, EntityTrait, Select};
-use tracing::error;
+use chrono::{DateTime, FixedOffset};
+use sea_orm::{
+    ActiveModelTrait, DbErr, EntityTrait, Iterable, PrimaryKeyToColumn, PrimaryKeyTrait,
+    QueryTrait, Select,
+};
+use tokio::sync::oneshot::{self, error::TryRecvError};
+use tracing::{error, warn};
 
 use crate::{
     carrier::{
         execute::{ExecuteCarrier, HasExecuteCarrier},
-        query::ImplQueryCarrier,
+        query::{HasQueryCarrier, ImplQueryCarrier, StreamedRows},
         simple_query::{HasSimpleQueryCarrier, ImplSimpleQueryCarrier, SimpleQueryCarrier},
     },
     container::builder::ContainerBuilder,
-    FromEntity, ToEntity,
+    FromEntity, ToActiveModel, ToEntity,
 };
 
-use super::data::{Data, HasData};
+use super::data::{patch::DataPatch, Data, HasData, ImplData};
 
 pub struct ProjectingContainer<Value, DbValue>
 where
@@ -25,13 +30,35 @@ where
     pub data: Data<Value>,
     query_carrier: SimpleQueryCarrier<DbValue>,
     execute_carrier: ExecuteCarrier,
+    optimistic: Option<Optimistic<Value>>,
+}
+
+/// Optimistic updates that are shown until a query result includes them
+struct Optimistic<Value> {
+    /// The values from the database, query results that arrive while updates
+    /// are pending are stored here instead of being shown
+    confirmed: Arc<[Value]>,
+    /// When the query of `confirmed` started, `None` if no query result was
+    /// stored since the first update
+    confirmed_started: Option<DateTime<FixedOffset>>,
+    /// When the last of the finished executes finished
+    finished: Option<DateTime<FixedOffset>>,
+    pending: Vec<oneshot::Receiver<Option<DateTime<FixedOffset>>>>,
+}
+
+impl<Value> Optimistic<Value> {
+    /// Returns if a query started at `started` already sees all updates,
+    /// which is only known once none of them is pending anymore
+    fn is_seen_by(&self, started: DateTime<FixedOffset>) -> bool {
+        self.pending.is_empty() && self.finished.is_some_and(|finished| started > finished)
+    }
 }
 
 impl<Value, DbValue> ProjectingContainer<Value, DbValue>
 where
     Value: Clone + Send + 'static,
     DbValue: EntityTrait + Send + 'static,
-    <DbValue as EntityTrait>::Model: FromEntity<Value> + ToEntity<Value>,
+    <DbValue as EntityTrait>::Model: FromEntity<Value> + ToEntity<Value> + Sync,
 {
     pub(crate) fn from_carriers(
         name: String,
@@ -43,6 +70,7 @@ where
             data: Data::default(),
             query_carrier,
             execute_carrier,
+            optimistic: None,
         }
     }
 
@@ -50,20 +78,132 @@ where
         self.query_carrier.builder()
     }
 
+    /// Applies `patch` to the data right away and runs `execute` in the
+    /// background. If the execute fails, all pending optimistic updates are
+    /// reverted to the last values from the database and the error can be
+    /// read with [last_execute_error]. Otherwise the values stay until a
+    /// query that started after all executes finished replaces them, results
+    /// of earlier queries are dropped since they might not include the
+    /// updates.
+    ///
+    /// [last_execute_error]: crate::carrier::execute::ImplExecuteError::last_execute_error
+    pub fn optimistic_update(
+        &mut self,
+        patch: DataPatch<Value>,
+        execute: impl QueryTrait + Send + 'static,
+    ) {
+        let confirmed = self.data().clone();
+        let optimistic = self.optimistic.get_or_insert_with(|| Optimistic {
+            confirmed,
+            confirmed_started: None,
+            finished: None,
+            pending: vec![],
+        });
+        optimistic
+            .pending
+            .push(self.execute_carrier.execute_awaited(execute));
+        self.data.patch(patch);
+    }
+
     pub fn state_update(&mut self, automatic_requery: bool) {
         self.query_carrier.try_recive_should_update();
+        self.resolve_optimistic();
         if let Some(result) = self.query_carrier.try_resolve_query() {
             match result {
-                Ok(values) => self.data.set(values.into_iter().map(ToEntity::to_entity)),
+                Ok(values) => self.set_confirmed(values.iter().cloned(), false, self.started()),
+                Err(error) => error!(container = self.name, error = error.to_string()),
+            }
+        }
+        if let Some(result) = self.query_carrier.try_resolve_stream() {
+            match result {
+                Ok(StreamedRows::First(values)) => {
+                    self.set_confirmed(values.into_iter(), false, self.started())
+                }
+                Ok(StreamedRows::More(values)) => {
+                    self.set_confirmed(values.into_iter(), true, self.started())
+                }
                 Err(error) => error!(container = self.name, error = error.to_string()),
             }
         }
         self.execute_carrier.try_resolve_executes();
 
         if automatic_requery && self.should_refresh() {
-            if let Some(select) = &self.query_carrier.stored_select {
-                self.query(select.clone());
+            self.query_carrier.rerun_stored_query();
+        }
+    }
+
+    /// When the query of the result that was just resolved started
+    fn started(&self) -> DateTime<FixedOffset> {
+        self.query_carrier
+            .ref_query_carrier()
+            .resolved_started()
+            .expect("a result was just resolved")
+    }
+
+    /// Shows values queried at `started`, or stores them until the pending
+    /// optimistic updates are done. Values of a query that started before the
+    /// updates finished are dropped once none is pending anymore.
+    fn set_confirmed(
+        &mut self,
+        values: impl Iterator<Item = DbValue::Model>,
+        append: bool,
+        started: DateTime<FixedOffset>,
+    ) {
+        let values = values.map(ToEntity::to_entity);
+        match self.optimistic.as_mut() {
+            Some(optimistic) if optimistic.is_seen_by(started) => {
+                self.optimistic = None;
+            }
+            // the requery after the updates is still to come
+            Some(optimistic) if optimistic.pending.is_empty() => return,
+            Some(optimistic) => {
+                optimistic.confirmed = match append {
+                    true => optimistic.confirmed.iter().cloned().chain(values).collect(),
+                    false => values.collect(),
+                };
+                let _ = optimistic.confirmed_started.insert(started);
+                return;
             }
+            None => (),
+        }
+        match append {
+            true => self.data.append(values.collect()),
+            false => self.data.set(values),
+        }
+    }
+
+    fn resolve_optimistic(&mut self) {
+        let Some(optimistic) = self.optimistic.as_mut() else {
+            return;
+        };
+        let mut failed = false;
+        optimistic
+            .pending
+            .retain_mut(|reciver| match reciver.try_recv() {
+                Ok(Some(finished)) => {
+                    optimistic.finished = optimistic.finished.max(Some(finished));
+                    false
+                }
+                // an execute that was dropped might not have run
+                Ok(None) | Err(TryRecvError::Closed) => {
+                    failed = true;
+                    false
+                }
+                Err(TryRecvError::Empty) => true,
+            });
+
+        if failed {
+            warn!(container = self.name, "reverting optimistic updates");
+            let optimistic = self.optimistic.take().expect("checked above");
+            self.data.set(optimistic.confirmed.iter().cloned());
+            // executes that were still pending may have succeeded
+            self.query_carrier.rerun_stored_query();
+        } else if optimistic
+            .confirmed_started
+            .is_some_and(|started| optimistic.is_seen_by(started))
+        {
+            let optimistic = self.optimistic.take().expect("checked above");
+            self.data.set(optimistic.confirmed.iter().cloned());
         }
     }
 
@@ -84,6 +224,51 @@ where
     }
 }
 
+/// Write-back of projected values, the executes invalidate the table like any
+/// other execute so the change shows up with the next requery
+impl<Value, DbValue, ActiveModel> ProjectingContainer<Value, DbValue>
+where
+    Value: Clone + Send + 'static + ToActiveModel<ActiveModel = ActiveModel>,
+    DbValue: EntityTrait + Send + 'static,
+    <DbValue as EntityTrait>::Model: FromEntity<Value> + ToEntity<Value>,
+    ActiveModel: ActiveModelTrait<Entity = DbValue> + Send + 'static,
+{
+    /// Updates the row of `value`, found by its primary key, with all fields
+    /// of `value`
+    pub fn save(&mut self, value: &Value) {
+        self.execute_carrier
+            .execute(DbValue::update(Self::update_model(value)));
+    }
+
+    /// Inserts `value` as a new row, an auto incrementing primary key is left
+    /// to the database
+    pub fn insert(&mut self, value: Value) {
+        self.execute_carrier
+            .execute(DbValue::insert(Self::insert_model(value)));
+    }
+
+    /// Deletes the row of `value`, found by its primary key
+    pub fn delete(&mut self, value: &Value) {
+        self.execute_carrier
+            .execute(DbValue::delete(value.dml_clone()));
+    }
+
+    /// Every field is set, so that the update writes all of them
+    fn update_model(value: &Value) -> ActiveModel {
+        value.dml_clone().reset_all()
+    }
+
+    fn insert_model(value: Value) -> ActiveModel {
+        let mut model = value.dml();
+        if <DbValue::PrimaryKey as PrimaryKeyTrait>::auto_increment() {
+            for key in DbValue::PrimaryKey::iter() {
+                model.not_set(key.into_column());
+            }
+        }
+        model
+    }
+}
+
 impl<Value, DbValue> HasSimpleQueryCarrier<DbValue> for ProjectingContainer<Value, DbValue>
 where
     Value: Send,
@@ -138,6 +323,196 @@ where
             data: Data::default(),
             query_carrier: self.query_carrier.clone(),
             execute_carrier: self.execute_carrier.clone(),
+            optimistic: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::Local;
+    use sea_orm::{ActiveValue, DbErr, EntityTrait};
+    use tokio::{
+        sync::{mpsc, oneshot},
+        task::yield_now,
+    };
+
+    use super::ProjectingContainer;
+    use crate::{
+        carrier::{execute::ImplExecuteError, shared_query::SharedQueries},
+        container::{
+            builder::ContainerBuilder,
+            data::{patch::DataPatch, ImplData},
+        },
+        test_db::{
+            self, all_tables,
+            task::{self, Task},
+            FakeDatabase,
+        },
+        ToEntity,
+    };
+
+    type Tasks = ProjectingContainer<Task, task::Entity>;
+
+    fn container(fake: &Arc<FakeDatabase>) -> Tasks {
+        let (tables_changed_sender, _) = mpsc::channel(10);
+        let (new_register_sender, _) = mpsc::channel(10);
+        let mut container = ContainerBuilder::new(
+            test_db::connect(fake),
+            all_tables(),
+            tables_changed_sender,
+            new_register_sender,
+            SharedQueries::default(),
+        )
+        .projector();
+        container.data.set([task(1, "task 1")].into_iter());
+        container
+    }
+
+    fn task(id: i32, title: &str) -> Task {
+        Task {
+            id,
+            project_id: None,
+            title: title.to_string(),
+        }
+    }
+
+    fn edit(container: &mut Tasks, title: &str) {
+        let patch = DataPatch::new().update(0, task(1, title));
+        container.optimistic_update(patch, task::Entity::delete_many());
+    }
+
+    async fn resolve(container: &mut Tasks) {
+        while container
+            .optimistic
+            .as_ref()
+            .is_some_and(|optimistic| !optimistic.pending.is_empty())
+        {
+            yield_now().await;
+            container.state_update(false);
+        }
+    }
+
+    #[tokio::test]
+    async fn update_is_shown_until_confirmed() {
+        let fake = Arc::new(FakeDatabase::default());
+        let mut container = container(&fake);
+
+        edit(&mut container, "edited");
+        assert_eq!(container.data()[..], [task(1, "edited")]);
+
+        resolve(&mut container).await;
+        assert_eq!(container.data()[..], [task(1, "edited")]);
+        assert!(container.last_execute_error().is_none());
+    }
+
+    #[tokio::test]
+    async fn failed_update_is_reverted() {
+        let fake = Arc::new(FakeDatabase::default());
+        fake.push_execute(Err(DbErr::Custom("constraint".to_string())));
+        let mut container = container(&fake);
+
+        edit(&mut container, "edited");
+        resolve(&mut container).await;
+        assert_eq!(container.data()[..], [task(1, "task 1")]);
+        assert!(container.last_execute_error().is_some());
+    }
+
+    #[tokio::test]
+    async fn dropped_update_is_reverted() {
+        let fake = Arc::new(FakeDatabase::default());
+        let mut container = container(&fake);
+
+        edit(&mut container, "edited");
+        let (_, dropped) = oneshot::channel();
+        container.optimistic.as_mut().unwrap().pending.push(dropped);
+        resolve(&mut container).await;
+        assert_eq!(container.data()[..], [task(1, "task 1")]);
+    }
+
+    #[test]
+    fn save_sets_every_field() {
+        let model = Tasks::update_model(&task(1, "edited"));
+        assert_eq!(model.id, ActiveValue::Set(1));
+        assert_eq!(model.project_id, ActiveValue::Set(None));
+        assert_eq!(model.title, ActiveValue::Set("edited".to_string()));
+    }
+
+    #[test]
+    fn insert_leaves_auto_increment_key_to_the_database() {
+        let model = Tasks::insert_model(task(1, "new"));
+        assert_eq!(model.id, ActiveValue::NotSet);
+        assert_eq!(model.title, ActiveValue::Unchanged("new".to_string()));
+    }
+
+    #[tokio::test]
+    async fn executes_are_built_from_values() {
+        let fake = Arc::new(FakeDatabase::default());
+        let mut container = container(&fake);
+        container.save(&task(1, "edited"));
+        container.insert(task(1, "new"));
+        container.delete(&task(1, "edited"));
+        while fake.statements().len() < 3 {
+            yield_now().await;
         }
+
+        let mut statements = fake.statements();
+        statements.sort();
+        let [delete, insert, update] = &statements[..] else {
+            panic!("expected three statements: {statements:?}");
+        };
+        assert!(delete.starts_with("DELETE") && delete.ends_with(" = 1"));
+        assert!(insert.starts_with("INSERT") && insert.ends_with("VALUES (NULL, 'new')"));
+        assert!(update.starts_with("UPDATE") && update.contains(" = NULL, "));
+        assert!(update.contains(" = 'edited' WHERE ") && update.ends_with(" = 1"));
+    }
+
+    #[tokio::test]
+    async fn only_queries_started_after_the_updates_are_shown() {
+        let fake = Arc::new(FakeDatabase::default());
+        let mut container = container(&fake);
+        let started_before = Local::now().into();
+
+        edit(&mut container, "edited");
+        container.set_confirmed([task::model(2, None)].into_iter(), false, started_before);
+        assert_eq!(container.data()[..], [task(1, "edited")]);
+
+        resolve(&mut container).await;
+        assert_eq!(container.data()[..], [task(1, "edited")]);
+        container.set_confirmed([task::model(3, None)].into_iter(), false, started_before);
+        assert_eq!(container.data()[..], [task(1, "edited")]);
+
+        container.set_confirmed(
+            [task::model(4, None)].into_iter(),
+            false,
+            Local::now().into(),
+        );
+        assert_eq!(container.data()[..], [task::model(4, None).to_entity()]);
+        assert!(container.optimistic.is_none());
+    }
+
+    #[tokio::test]
+    async fn query_result_stored_while_pending_is_shown_if_it_saw_the_updates() {
+        let fake = Arc::new(FakeDatabase::default());
+        let mut container = container(&fake);
+
+        edit(&mut container, "edited");
+        while fake.statements().is_empty() {
+            yield_now().await;
+        }
+        // the execute finished, but it wasn't resolved yet
+        yield_now().await;
+        container.set_confirmed(
+            [task::model(2, None)].into_iter(),
+            false,
+            Local::now().into(),
+        );
+        assert_eq!(container.data()[..], [task(1, "edited")]);
+
+        resolve(&mut container).await;
+        assert_eq!(container.data()[..], [task::model(2, None).to_entity()]);
+        assert!(container.optimistic.is_none());
     }
 }
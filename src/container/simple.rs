@@ -1,10 +1,12 @@
+use std::sync::Arc;
+
 use sea_orm::EntityTrait;
 use tracing::error;
 
 use crate::{
     carrier::{
         execute::{ExecuteCarrier, HasExecuteCarrier},
-        query::ImplQueryCarrier,
+        query::{ImplQueryCarrier, StreamedRows},
         simple_query::{HasSimpleQueryCarrier, SimpleQueryCarrier},
     },
     container::builder::ContainerBuilder,
@@ -14,7 +16,7 @@ pub struct Container<DbValue>
 where
     DbValue: EntityTrait + Send + 'static,
 {
-    values: Vec<DbValue::Model>,
+    values: Arc<[DbValue::Model]>,
     query_carrier: SimpleQueryCarrier<DbValue>,
     execute_carrier: ExecuteCarrier,
 }
@@ -28,7 +30,7 @@ where
         execute_carrier: ExecuteCarrier,
     ) -> Self {
         Self {
-            values: vec![],
+            values: Arc::new([]),
             query_carrier,
             execute_carrier,
         }
@@ -39,6 +41,7 @@ where
     }
 
     pub fn state_update(&mut self) {
+        self.query_carrier.try_recive_should_update();
         if let Some(result) = self.query_carrier.try_resolve_query() {
             match result {
                 Ok(values) => {
@@ -47,6 +50,15 @@ where
                 Err(error) => error!("{error}"),
             }
         }
+        if let Some(result) = self.query_carrier.try_resolve_stream() {
+            match result {
+                Ok(StreamedRows::First(values)) => self.values = values.into(),
+                Ok(StreamedRows::More(values)) => {
+                    self.values = self.values.iter().cloned().chain(values).collect();
+                }
+                Err(error) => error!("{error}"),
+            }
+        }
         self.execute_carrier.try_resolve_executes();
     }
 
@@ -85,7 +97,7 @@ where
 {
     fn clone(&self) -> Self {
         Self {
-            values: vec![],
+            values: Arc::new([]),
             query_carrier: self.query_carrier.clone(),
             execute_carrier: self.execute_carrier.clone(),
         }
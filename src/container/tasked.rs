@@ -1,33 +1,43 @@
 mod awaiting_execute;
 mod awaiting_result;
 
-use std::future::Future;
+use std::{error::Error, future::Future, mem, sync::Arc};
 
 use chrono::Duration;
 use tokio::{
     sync::oneshot::{self},
     task,
 };
-use tracing::warn;
+use tracing::{debug, warn};
 
 use crate::{
     container::{
         create_name,
         data::{Data, HasData},
+        polling::Polling,
         tasked::{
-            awaiting_execute::AwaitingExecute,
+            awaiting_execute::{AwaitingExecute, AwaitingExecuteResult},
             awaiting_result::{AwaitingResult, AwaitingTask},
         },
     },
-    LogDebugErr, LogErr,
+    LogErr,
 };
 
+#[cfg(any(feature = "psql", feature = "mysql", feature = "sqlite"))]
+use crate::container::invalidation::Invalidation;
+
+/// Error of a failed execute started with [Container::try_execute_changes] or
+/// [Container::try_execute_any]
+pub type ExecuteFailure = Box<dyn Error + Send + Sync + 'static>;
+
 /// This [Container] can be used to run async tasks in the background
 /// and automantically have the data refresh upon finishing of the task.
 ///
-/// The principle is simple. You pass it a function pointer and a init parameter
-/// which then instantly starts fetching the data. Whenever you wan't, you can
-/// refetch that data or change the parameter
+/// The principle is simple. You pass it a fetcher function or closure and a
+/// init parameter which then instantly starts fetching the data. Whenever you
+/// wan't, you can refetch that data or change the parameter. If a fetch fails
+/// the previous data is kept and the error is available through
+/// [Container::last_error].
 ///
 /// ```rust
 /// use std::future::Future;
@@ -36,10 +46,10 @@ use crate::{
 ///
 /// pub fn my_fetcher_function(
 ///     parameter: String
-/// ) -> impl Future<Output = Vec<String>> + Send + 'static {
+/// ) -> impl Future<Output = Result<Vec<String>, String>> + Send + 'static {
 ///     async move {
 ///         sleep(Duration::from_millis(100)).await;
-///         vec![ parameter.clone(), parameter.clone(), parameter.clone() ]
+///         Ok(vec![ parameter.clone(), parameter.clone(), parameter.clone() ])
 ///     }
 /// }
 ///
@@ -62,66 +72,125 @@ use crate::{
 ///     );
 /// }
 /// ```
-pub struct Container<P, Fut, O>
+pub struct Container<P, Fut, O, E>
 where
     P: Clone + Send + 'static,
-    Fut: Future<Output = Vec<O>> + Send + 'static,
+    Fut: Future<Output = Result<Vec<O>, E>> + Send + 'static,
     O: Send + 'static,
+    E: Send + 'static,
 {
     name: String,
     param: P,
-    task_func: fn(P) -> Fut,
-    task: Option<AwaitingTask<O>>,
-    executes: Vec<AwaitingExecute>,
+    task_func: TaskFn<P, Fut>,
+    task: Option<AwaitingTask<O, E>>,
+    executes: Vec<AwaitingExecute<ExecuteFailure>>,
 
     data: Data<O>,
+    last_error: Option<E>,
+    last_execute_error: Option<ExecuteFailure>,
     timeout: Duration,
+    timed_out: bool,
+    polling: Polling,
+    #[cfg(any(feature = "psql", feature = "mysql", feature = "sqlite"))]
+    invalidation: Option<Invalidation>,
+}
+
+/// State of the fetch of a [Container]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    /// No fetch is running
+    Idle,
+    Fetching,
+    /// The last fetch took longer than the timeout and was aborted
+    TimedOut,
 }
 
-impl<P, Fut, O> Container<P, Fut, O>
+const DEFAULT_TIMEOUT: Duration = Duration::seconds(5);
+
+/// Fetcher used by the [Container], can be a plain function or a closure
+/// capturing for example a client or connection handle.
+pub type TaskFn<P, Fut> = Arc<dyn Fn(P) -> Fut + Send + Sync + 'static>;
+
+impl<P, Fut, O, E> Container<P, Fut, O, E>
 where
     P: Clone + Send + 'static,
-    Fut: Future<Output = Vec<O>> + Send + 'static,
+    Fut: Future<Output = Result<Vec<O>, E>> + Send + 'static,
     O: Send + 'static,
+    E: Send + 'static,
 {
     /// Creates a new [Container] instance with a default name. Will
     /// instantly start executing the first request with the initial parameters.
-    pub fn new_default_name(task_func: fn(P) -> Fut, init_param: P) -> Self {
+    pub fn new_default_name(
+        task_func: impl Fn(P) -> Fut + Send + Sync + 'static,
+        init_param: P,
+    ) -> Self {
         Self::new(create_name::<Self, O>(), task_func, init_param)
     }
 
     /// Creates a new [Container] instance with the defined parameters. Will
     /// instantly start executing the first request with the initial parameters.
-    pub fn new(name: String, task_func: fn(P) -> Fut, init_param: P) -> Self {
+    pub fn new(
+        name: String,
+        task_func: impl Fn(P) -> Fut + Send + Sync + 'static,
+        init_param: P,
+    ) -> Self {
+        Self::from_task_fn(name, Arc::new(task_func), init_param)
+    }
+
+    /// Same as [Container::new] but takes an already shared [TaskFn], so that
+    /// multiple containers can use the same fetcher.
+    pub fn from_task_fn(name: String, task_func: TaskFn<P, Fut>, init_param: P) -> Self {
         Self {
             name,
             param: init_param.clone(),
+            task: Some(Self::request(task_func.clone(), init_param)),
             task_func,
-            task: Some(Self::request(task_func, init_param)),
             executes: vec![],
             data: Data::default(),
-            timeout: Duration::seconds(5),
+            last_error: None,
+            last_execute_error: None,
+            timeout: DEFAULT_TIMEOUT,
+            timed_out: false,
+            polling: Polling::default(),
+            #[cfg(any(feature = "psql", feature = "mysql", feature = "sqlite"))]
+            invalidation: None,
         }
     }
 
+    /// Connects this container to the [Messenger](crate::messenger::Messenger),
+    /// see [ContainerBuilder::tasked](crate::container::builder::ContainerBuilder::tasked)
+    #[cfg(any(feature = "psql", feature = "mysql", feature = "sqlite"))]
+    pub(crate) fn with_invalidation(mut self, invalidation: Invalidation) -> Self {
+        let _ = self.invalidation.insert(invalidation);
+        self
+    }
+
     /// This function is what keeps everything running meaning it always needs
     /// to be called in direct render contexts.
     ///
     /// Specifically it takes care of checking if tasks completed and updating
-    /// the Data if they did.
+    /// the Data if they did. Once an execute started with
+    /// [Container::execute_changes] succeeded, the refresh interval passed or
+    /// one of the tables the container depends on changed the data is
+    /// requeried.
     pub fn state_update(&mut self) {
         self.task = self
             .task
             .take()
-            .map(|awaiter| match awaiter.try_resolve() {
-                AwaitingResult::Recived(values) => {
+            .and_then(|awaiter| match awaiter.try_resolve() {
+                AwaitingResult::Recived(Ok(values)) => {
                     self.data.set(values.into_iter());
+                    self.last_error = None;
+                    None
+                }
+                AwaitingResult::Recived(Err(error)) => {
+                    warn!("{} failed to fetch, keeping the previous data.", self.name);
+                    let _ = self.last_error.insert(error);
                     None
                 }
                 AwaitingResult::Waiting(awaiting_task) => Some(awaiting_task),
                 AwaitingResult::Closed => None,
-            })
-            .flatten();
+            });
 
         if self
             .task
@@ -129,8 +198,46 @@ where
             .map(|executing| executing.has_timed_out(self.timeout))
             .unwrap_or_default()
         {
+            warn!("{} timed out, aborting the fetch.", self.name);
             let _ = self.task.take();
+            self.timed_out = true;
         }
+
+        let should_poll = self.task.is_none() && self.polling.is_due();
+        if self.resolve_executes() || should_poll || self.tables_changed() {
+            self.refresh();
+        }
+    }
+
+    /// Returns if the [Messenger](crate::messenger::Messenger) notified about a
+    /// change to one of the tables this container depends on
+    fn tables_changed(&mut self) -> bool {
+        #[cfg(any(feature = "psql", feature = "mysql", feature = "sqlite"))]
+        if let Some(invalidation) = self.invalidation.as_mut() {
+            return invalidation.try_recive_changed();
+        }
+        false
+    }
+
+    /// Drops all finished executes and returns if any of the successfull ones
+    /// changed the data
+    fn resolve_executes(&mut self) -> bool {
+        let mut should_requery = false;
+        for execute in mem::take(&mut self.executes) {
+            let changes_data = execute.changes_data();
+            match execute.try_resolve() {
+                AwaitingExecuteResult::Finished(Ok(())) => should_requery |= changes_data,
+                AwaitingExecuteResult::Finished(Err(error)) => {
+                    warn!("{} failed to execute.", self.name);
+                    let _ = self.last_execute_error.insert(error);
+                }
+                AwaitingExecuteResult::Waiting(execute) => self.executes.push(execute),
+                AwaitingExecuteResult::Closed => {
+                    warn!("{} execute ended without a result.", self.name);
+                }
+            }
+        }
+        should_requery
     }
 
     /// Returns the name of this container
@@ -138,6 +245,73 @@ where
         &self.name
     }
 
+    /// Sets how long a fetch may take before it is aborted, defaults to five
+    /// seconds
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Builder version of [Container::set_timeout]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.set_timeout(timeout);
+        self
+    }
+
+    /// Sets the interval in which the data is refetched on its own, `None`
+    /// disables polling. Polling is done inside of [Container::state_update].
+    pub fn set_refresh_interval(&mut self, interval: Option<Duration>) {
+        self.polling.set_interval(interval);
+    }
+
+    /// Builder version of [Container::set_refresh_interval]
+    pub fn with_refresh_interval(mut self, interval: Duration) -> Self {
+        self.set_refresh_interval(Some(interval));
+        self
+    }
+
+    /// Stops polling until [Container::resume_polling] is called, for example
+    /// while the data is not visible
+    pub fn pause_polling(&mut self) {
+        self.polling.pause();
+    }
+
+    pub fn resume_polling(&mut self) {
+        self.polling.resume();
+    }
+
+    pub fn is_polling_paused(&self) -> bool {
+        self.polling.is_paused()
+    }
+
+    pub fn task_state(&self) -> TaskState {
+        match (&self.task, self.timed_out) {
+            (Some(_), _) => TaskState::Fetching,
+            (None, true) => TaskState::TimedOut,
+            (None, false) => TaskState::Idle,
+        }
+    }
+
+    /// Returns the error of the last fetch if it failed. Is reset as soon as
+    /// a fetch succeeds again.
+    pub fn last_error(&self) -> Option<&E> {
+        self.last_error.as_ref()
+    }
+
+    /// Returns the error of the last failed execute, is kept until the next
+    /// execute fails or [Container::clear_execute_error] is called.
+    pub fn last_execute_error(&self) -> Option<&(dyn Error + Send + Sync + 'static)> {
+        self.last_execute_error.as_deref()
+    }
+
+    pub fn clear_execute_error(&mut self) {
+        self.last_execute_error = None;
+    }
+
+    /// Returns if there are still executes running
+    pub fn is_executing(&self) -> bool {
+        !self.executes.is_empty()
+    }
+
     /// Sets a new value for the stored parameters and directly starts a new
     /// [Container::refresh] with the new parameters
     pub fn new_params(&mut self, new_params: P) {
@@ -145,28 +319,36 @@ where
         self.refresh();
     }
 
-    /// Starts a new fetch of data throught the function pointer and the
-    /// internally stored parameters. The parameters can be changed with
-    /// [Container::new_params]
+    /// Starts a new fetch of data throught the fetcher and the internally
+    /// stored parameters. The parameters can be changed with
+    /// [Container::new_params]. A fetch that is still running is aborted,
+    /// since its result would be outdated anyway.
     pub fn refresh(&mut self) {
-        if self.task.is_some() {
-            warn!("{} is already executing, can't refresh.", self.name);
+        if self.task.take().is_some() {
+            debug!(
+                "{} is already fetching, aborting the previous fetch.",
+                self.name
+            );
         }
-        let task = Self::request(self.task_func, self.param.clone());
+        let task = Self::request(self.task_func.clone(), self.param.clone());
         let _ = self.task.insert(task);
+        self.timed_out = false;
+        self.polling.restart();
     }
 
     /// Allows you to execute some async function. Will automatically requery
-    /// data with the stored functionpointer after completion. This means that
-    /// the function executed is expected to change the data.
+    /// data with the stored fetcher after completion. This means that the
+    /// function executed is expected to change the data.
     ///
     /// If you don't intend to requery after execution use [Container::execute_any]
     pub fn execute_changes<ExF>(&mut self, exec_fn: impl FnOnce() -> ExF + Send + 'static)
     where
         ExF: Future<Output = ()> + Send + 'static,
     {
-        let execute = Self::execute(exec_fn, true);
-        self.executes.push(execute);
+        self.try_execute_changes(move || async move {
+            exec_fn().await;
+            Ok::<_, ExecuteFailure>(())
+        });
     }
 
     /// Allows you to execute some async function. Will not requery the internal
@@ -176,47 +358,76 @@ where
     pub fn execute_any<ExF>(&mut self, exec_fn: impl FnOnce() -> ExF + Send + 'static)
     where
         ExF: Future<Output = ()> + Send + 'static,
+    {
+        self.try_execute_any(move || async move {
+            exec_fn().await;
+            Ok::<_, ExecuteFailure>(())
+        });
+    }
+
+    /// Fallible version of [Container::execute_changes], the data is only
+    /// requeried if the execute succeeded. Failures are available through
+    /// [Container::last_execute_error].
+    pub fn try_execute_changes<ExF, ExE>(&mut self, exec_fn: impl FnOnce() -> ExF + Send + 'static)
+    where
+        ExF: Future<Output = Result<(), ExE>> + Send + 'static,
+        ExE: Into<ExecuteFailure>,
+    {
+        let execute = Self::execute(exec_fn, true);
+        self.executes.push(execute);
+    }
+
+    /// Fallible version of [Container::execute_any], failures are available
+    /// through [Container::last_execute_error].
+    pub fn try_execute_any<ExF, ExE>(&mut self, exec_fn: impl FnOnce() -> ExF + Send + 'static)
+    where
+        ExF: Future<Output = Result<(), ExE>> + Send + 'static,
+        ExE: Into<ExecuteFailure>,
     {
         let execute = Self::execute(exec_fn, false);
         self.executes.push(execute);
     }
 
-    fn execute<ExF>(
+    fn execute<ExF, ExE>(
         exec_fn: impl FnOnce() -> ExF + Send + 'static,
         changes_data: bool,
-    ) -> AwaitingExecute
+    ) -> AwaitingExecute<ExecuteFailure>
     where
-        ExF: Future<Output = ()> + Send + 'static,
+        ExF: Future<Output = Result<(), ExE>> + Send + 'static,
+        ExE: Into<ExecuteFailure>,
     {
         let (sender, reciver) = oneshot::channel();
 
         task::spawn(async move {
-            let _ = exec_fn().await;
-            sender.send(()).log_err();
+            let result = exec_fn().await.map_err(Into::into);
+            sender
+                .send(result)
+                .log_msg("was unable to send execute result");
         });
 
         AwaitingExecute::new(changes_data, reciver)
     }
 
-    fn request(task_func: fn(P) -> Fut, param: P) -> AwaitingTask<O> {
+    fn request(task_func: TaskFn<P, Fut>, param: P) -> AwaitingTask<O, E> {
         let (sender, receiver) = oneshot::channel();
 
-        task::spawn(async move {
+        let handle = task::spawn(async move {
             let result = task_func(param).await;
             sender
                 .send(result)
                 .log_msg("was unable to send new fetched data");
         });
 
-        AwaitingTask::new(receiver)
+        AwaitingTask::new(receiver, handle)
     }
 }
 
-impl<P, Fut, O> HasData<O> for Container<P, Fut, O>
+impl<P, Fut, O, E> HasData<O> for Container<P, Fut, O, E>
 where
     P: Clone + Send + 'static,
-    Fut: Future<Output = Vec<O>> + Send + 'static,
+    Fut: Future<Output = Result<Vec<O>, E>> + Send + 'static,
     O: Send + 'static,
+    E: Send + 'static,
 {
     fn ref_data(&self) -> &Data<O> {
         &self.data
@@ -229,16 +440,29 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::container::{data::ImplData, tasked::Container};
-    use std::future::Future;
+    use crate::container::{
+        data::ImplData,
+        tasked::{Container, TaskState},
+    };
+    use std::{
+        future::Future,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        },
+    };
 
-    use tokio::time::{sleep, Duration};
+    use tokio::{
+        task::yield_now,
+        time::{advance, sleep, Duration},
+    };
 
-    pub fn test(_: String) -> impl Future<Output = Vec<()>> + Send + 'static {
+    #[allow(clippy::manual_async_fn)]
+    pub fn test(_: String) -> impl Future<Output = Result<Vec<()>, ()>> + Send + 'static {
         #[allow(clippy::unused_unit)]
         async move {
             sleep(Duration::from_millis(10)).await;
-            vec![()]
+            Ok(vec![()])
         }
     }
 
@@ -261,10 +485,17 @@ mod tests {
     async fn can_await() {
         const CONTENT: &str = "some very random content that";
 
-        pub fn my_fn(parameter: String) -> impl Future<Output = Vec<String>> + Send + 'static {
+        #[allow(clippy::manual_async_fn)]
+        pub fn my_fn(
+            parameter: String,
+        ) -> impl Future<Output = Result<Vec<String>, ()>> + Send + 'static {
             async move {
                 sleep(Duration::from_millis(10)).await;
-                vec![parameter.clone(), parameter.clone(), parameter.clone()]
+                Ok(vec![
+                    parameter.clone(),
+                    parameter.clone(),
+                    parameter.clone(),
+                ])
             }
         }
 
@@ -281,11 +512,134 @@ mod tests {
         );
     }
 
-    async fn await_data_change<P, Fut, O>(cont: &mut Container<P, Fut, O>)
+    #[tokio::test]
+    async fn closure_errors_keep_previous_data() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let fetcher_calls = calls.clone();
+        let mut container = Container::new_default_name(
+            move |fail: bool| {
+                let call = fetcher_calls.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    match fail {
+                        true => Err(format!("call {call} failed")),
+                        false => Ok(vec![call]),
+                    }
+                }
+            },
+            false,
+        );
+        await_data_change(&mut container).await;
+        assert_eq!(vec![0], container.data().to_vec());
+
+        container.new_params(true);
+        while container.last_error().is_none() {
+            container.state_update();
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(Some(&String::from("call 1 failed")), container.last_error());
+        assert_eq!(vec![0], container.data().to_vec());
+
+        container.new_params(false);
+        await_data_change(&mut container).await;
+        assert!(container.last_error().is_none());
+        assert_eq!(vec![2], container.data().to_vec());
+        assert_eq!(3, calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn successful_changing_executes_requery() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let fetcher_calls = calls.clone();
+        let mut container = Container::new_default_name(
+            move |_: ()| {
+                let call = fetcher_calls.fetch_add(1, Ordering::SeqCst);
+                async move { Ok::<_, String>(vec![call]) }
+            },
+            (),
+        );
+        await_data_change(&mut container).await;
+
+        container.try_execute_any(|| async { Err(String::from("failed")) });
+        container.execute_changes(|| async {});
+        await_data_change(&mut container).await;
+
+        assert_eq!(vec![1], container.data().to_vec());
+        assert_eq!(
+            Some(String::from("failed")),
+            container.last_execute_error().map(ToString::to_string)
+        );
+        assert!(!container.is_executing());
+    }
+
+    #[tokio::test]
+    async fn timed_out_fetch_is_aborted() {
+        let finished = Arc::new(AtomicBool::new(false));
+        let fetcher_finished = finished.clone();
+        let mut container = Container::new_default_name(
+            move |_: ()| {
+                let finished = fetcher_finished.clone();
+                async move {
+                    sleep(Duration::from_millis(50)).await;
+                    finished.store(true, Ordering::SeqCst);
+                    Ok::<_, ()>(vec![()])
+                }
+            },
+            (),
+        )
+        .with_timeout(chrono::Duration::milliseconds(10));
+        assert_eq!(TaskState::Fetching, container.task_state());
+
+        sleep(Duration::from_millis(20)).await;
+        container.state_update();
+        assert_eq!(TaskState::TimedOut, container.task_state());
+
+        sleep(Duration::from_millis(60)).await;
+        assert!(!finished.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn polls_until_paused() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let fetcher_calls = calls.clone();
+        let mut container = Container::new_default_name(
+            move |_: ()| {
+                fetcher_calls.fetch_add(1, Ordering::SeqCst);
+                async { Ok::<_, ()>(vec![()]) }
+            },
+            (),
+        )
+        .with_refresh_interval(chrono::Duration::milliseconds(10));
+        yield_now().await;
+        container.state_update();
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+
+        advance(Duration::from_millis(5)).await;
+        container.state_update();
+        yield_now().await;
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+
+        for polled in 1..=3 {
+            advance(Duration::from_millis(10)).await;
+            container.state_update();
+            yield_now().await;
+            assert_eq!(1 + polled, calls.load(Ordering::SeqCst));
+        }
+
+        container.pause_polling();
+        for _ in 0..3 {
+            advance(Duration::from_millis(10)).await;
+            container.state_update();
+            yield_now().await;
+        }
+        assert_eq!(4, calls.load(Ordering::SeqCst));
+    }
+
+    async fn await_data_change<P, Fut, O, E>(cont: &mut Container<P, Fut, O, E>)
     where
         P: Clone + Send + 'static,
-        Fut: Future<Output = Vec<O>> + Send + 'static,
+        Fut: Future<Output = Result<Vec<O>, E>> + Send + 'static,
         O: Send + 'static,
+        E: Send + 'static,
     {
         cont.set_viewed();
         while !cont.has_changed() {
@@ -1,15 +1,42 @@
-use tokio::sync::oneshot;
+use tokio::sync::oneshot::{self, error::TryRecvError};
 
-pub struct AwaitingExecute {
+pub struct AwaitingExecute<E>
+where
+    E: Send + 'static,
+{
     changes_data: bool,
-    reciver: oneshot::Receiver<()>,
+    reciver: oneshot::Receiver<Result<(), E>>,
 }
 
-impl AwaitingExecute {
-    pub fn new(changes_data: bool, reciver: oneshot::Receiver<()>) -> Self {
+pub enum AwaitingExecuteResult<E>
+where
+    E: Send + 'static,
+{
+    Finished(Result<(), E>),
+    Waiting(AwaitingExecute<E>),
+    Closed,
+}
+
+impl<E> AwaitingExecute<E>
+where
+    E: Send + 'static,
+{
+    pub fn new(changes_data: bool, reciver: oneshot::Receiver<Result<(), E>>) -> Self {
         Self {
             changes_data,
             reciver,
         }
     }
+
+    pub fn changes_data(&self) -> bool {
+        self.changes_data
+    }
+
+    pub fn try_resolve(mut self) -> AwaitingExecuteResult<E> {
+        match self.reciver.try_recv() {
+            Ok(result) => AwaitingExecuteResult::Finished(result),
+            Err(TryRecvError::Empty) => AwaitingExecuteResult::Waiting(self),
+            Err(TryRecvError::Closed) => AwaitingExecuteResult::Closed,
+        }
+    }
 }
@@ -1,35 +1,48 @@
 use chrono::{DateTime, Duration, Local};
-use tokio::sync::oneshot::{self, error::TryRecvError};
+use tokio::{
+    sync::oneshot::{self, error::TryRecvError},
+    task::JoinHandle,
+};
 
-pub struct AwaitingTask<O>
+/// A running fetch. The spawned task is aborted as soon as this is dropped,
+/// so timed out or superseded fetches do not keep running in the background.
+pub struct AwaitingTask<O, E>
 where
     O: Send + 'static,
+    E: Send + 'static,
 {
-    result_reciver: oneshot::Receiver<Vec<O>>,
+    result_reciver: oneshot::Receiver<Result<Vec<O>, E>>,
+    handle: JoinHandle<()>,
     time_started: DateTime<Local>,
 }
 
-pub enum AwaitingResult<O>
+pub enum AwaitingResult<O, E>
 where
     O: Send + 'static,
+    E: Send + 'static,
 {
-    Recived(Vec<O>),
-    Waiting(AwaitingTask<O>),
+    Recived(Result<Vec<O>, E>),
+    Waiting(AwaitingTask<O, E>),
     Closed,
 }
 
-impl<O> AwaitingTask<O>
+impl<O, E> AwaitingTask<O, E>
 where
     O: Send + 'static,
+    E: Send + 'static,
 {
-    pub fn new(result_reciver: oneshot::Receiver<Vec<O>>) -> Self {
+    pub fn new(
+        result_reciver: oneshot::Receiver<Result<Vec<O>, E>>,
+        handle: JoinHandle<()>,
+    ) -> Self {
         Self {
             result_reciver,
+            handle,
             time_started: Local::now(),
         }
     }
 
-    pub fn try_resolve(mut self) -> AwaitingResult<O> {
+    pub fn try_resolve(mut self) -> AwaitingResult<O, E> {
         match self.result_reciver.try_recv() {
             Ok(val) => AwaitingResult::Recived(val),
             Err(TryRecvError::Empty) => AwaitingResult::Waiting(self),
@@ -41,3 +54,13 @@ where
         (Local::now() - self.time_started) > period
     }
 }
+
+impl<O, E> Drop for AwaitingTask<O, E>
+where
+    O: Send + 'static,
+    E: Send + 'static,
+{
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
@@ -0,0 +1,236 @@
+use std::{hash::Hash, sync::Arc};
+
+use sea_orm::EntityTrait;
+use tracing::error;
+
+use crate::carrier::{
+    execute::{ExecuteCarrier, HasExecuteCarrier},
+    query::{ImplQueryCarrier, StreamedRows},
+    simple_query::{HasSimpleQueryCarrier, ImplSimpleQueryCarrier, SimpleQueryCarrier},
+};
+
+use super::{
+    builder::ContainerBuilder,
+    data::{tree::TreeView, Data, HasData, ImplData},
+};
+
+type KeyFn<Model, K> = Arc<dyn Fn(&Model) -> K + Sync + Send + 'static>;
+
+/// Container for a self-referencing table, where every row points to its
+/// parent by key. The rows are arranged as a tree on every refresh, see
+/// [ImplData::tree_by] for how the tree behaves.
+pub struct TreeContainer<DbValue, K>
+where
+    DbValue: EntityTrait + Send + 'static,
+    K: Clone + Hash + Eq + Sync + Send + 'static,
+{
+    pub name: String,
+    pub data: Data<DbValue::Model>,
+    query_carrier: SimpleQueryCarrier<DbValue>,
+    execute_carrier: ExecuteCarrier,
+    key_fn: KeyFn<DbValue::Model, K>,
+    parent_fn: KeyFn<DbValue::Model, Option<K>>,
+}
+
+impl<DbValue, K> TreeContainer<DbValue, K>
+where
+    DbValue: EntityTrait + Send + 'static,
+    DbValue::Model: Sync,
+    K: Clone + Hash + Eq + Sync + Send + 'static,
+{
+    pub(crate) fn from_carriers(
+        name: String,
+        query_carrier: SimpleQueryCarrier<DbValue>,
+        execute_carrier: ExecuteCarrier,
+        key_fn: KeyFn<DbValue::Model, K>,
+        parent_fn: KeyFn<DbValue::Model, Option<K>>,
+    ) -> Self {
+        let mut container = Self {
+            name,
+            data: Data::default(),
+            query_carrier,
+            execute_carrier,
+            key_fn,
+            parent_fn,
+        };
+        let (key_fn, parent_fn) = (container.key_fn.clone(), container.parent_fn.clone());
+        container.tree_by(move |model| key_fn(model), move |model| parent_fn(model));
+        container
+    }
+
+    pub fn builder(&self) -> ContainerBuilder {
+        self.query_carrier.builder()
+    }
+
+    /// The tree of the rows, only `None` if it was replaced through
+    /// [ImplData::tree_by] with one of a different key type
+    pub fn tree(&self) -> Option<TreeView<'_, K, DbValue::Model>> {
+        self.data.tree()
+    }
+
+    pub fn state_update(&mut self, automatic_requery: bool) {
+        self.query_carrier.try_recive_should_update();
+        if let Some(result) = self.query_carrier.try_resolve_query() {
+            match result {
+                Ok(values) => self.data.set_shared(values),
+                Err(error) => error!(container = self.name, error = error.to_string()),
+            }
+        }
+        if let Some(result) = self.query_carrier.try_resolve_stream() {
+            match result {
+                Ok(StreamedRows::First(values)) => self.data.set_shared(values.into()),
+                Ok(StreamedRows::More(values)) => self.data.append(values),
+                Err(error) => error!(container = self.name, error = error.to_string()),
+            }
+        }
+        self.execute_carrier.try_resolve_executes();
+
+        if automatic_requery && self.should_refresh() {
+            self.query_carrier.rerun_stored_query();
+        }
+    }
+}
+
+impl<DbValue, K> HasSimpleQueryCarrier<DbValue> for TreeContainer<DbValue, K>
+where
+    DbValue: EntityTrait + Send + 'static,
+    K: Clone + Hash + Eq + Sync + Send + 'static,
+{
+    fn ref_simple_query_carrier(&self) -> &SimpleQueryCarrier<DbValue> {
+        &self.query_carrier
+    }
+    fn ref_mut_simple_query_carrier(&mut self) -> &mut SimpleQueryCarrier<DbValue> {
+        &mut self.query_carrier
+    }
+}
+
+impl<DbValue, K> HasExecuteCarrier for TreeContainer<DbValue, K>
+where
+    DbValue: EntityTrait + Send + 'static,
+    K: Clone + Hash + Eq + Sync + Send + 'static,
+{
+    fn ref_execute_carrier(&self) -> &ExecuteCarrier {
+        &self.execute_carrier
+    }
+    fn ref_mut_execute_carrier(&mut self) -> &mut ExecuteCarrier {
+        &mut self.execute_carrier
+    }
+}
+
+impl<DbValue, K> HasData<DbValue::Model> for TreeContainer<DbValue, K>
+where
+    DbValue: EntityTrait + Send + 'static,
+    K: Clone + Hash + Eq + Sync + Send + 'static,
+{
+    fn ref_data(&self) -> &Data<DbValue::Model> {
+        &self.data
+    }
+    fn ref_mut_data(&mut self) -> &mut Data<DbValue::Model> {
+        &mut self.data
+    }
+}
+
+impl<DbValue, K> Clone for TreeContainer<DbValue, K>
+where
+    DbValue: EntityTrait + Send + 'static,
+    DbValue::Model: Sync,
+    K: Clone + Hash + Eq + Sync + Send + 'static,
+{
+    fn clone(&self) -> Self {
+        Self::from_carriers(
+            self.name.clone(),
+            self.query_carrier.clone(),
+            self.execute_carrier.clone(),
+            self.key_fn.clone(),
+            self.parent_fn.clone(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use sea_orm::EntityTrait;
+    use tokio::{sync::mpsc, task::yield_now};
+
+    use super::TreeContainer;
+    use crate::{
+        carrier::{shared_query::SharedQueries, simple_query::ImplSimpleQueryCarrier},
+        container::{builder::ContainerBuilder, data::ImplData},
+        test_db::{self, all_tables, row, task, FakeDatabase},
+    };
+
+    type Tasks = TreeContainer<task::Entity, i32>;
+
+    /// Tasks as a tree, with the project id pointing to the parent task
+    fn container(fake: &Arc<FakeDatabase>) -> Tasks {
+        let (tables_changed_sender, _) = mpsc::channel(10);
+        let (new_register_sender, _) = mpsc::channel(10);
+        ContainerBuilder::new(
+            test_db::connect(fake),
+            all_tables(),
+            tables_changed_sender,
+            new_register_sender,
+            SharedQueries::default(),
+        )
+        .tree::<task::Entity, _>(|task| task.id, |task| task.project_id)
+    }
+
+    async fn query(container: &mut Tasks, fake: &FakeDatabase, tasks: &[task::Model]) {
+        fake.push_query(Ok(tasks.iter().map(row).collect()));
+        container.query(task::Entity::find());
+        let generation = container.generation();
+        while container.generation() == generation {
+            yield_now().await;
+            container.state_update(false);
+        }
+    }
+
+    fn flat_ids(container: &Tasks) -> Vec<(i32, usize)> {
+        container
+            .tree()
+            .unwrap()
+            .flatten()
+            .iter()
+            .map(|node| (*node.node.key(), node.depth))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn tree_is_rebuilt_when_rows_arrive() {
+        let fake = Arc::new(FakeDatabase::default());
+        let mut container = container(&fake);
+        query(&mut container, &fake, &[task::model(1, None)]).await;
+        assert_eq!(flat_ids(&container), [(1, 0)]);
+
+        query(
+            &mut container,
+            &fake,
+            &[task::model(1, None), task::model(2, Some(1))],
+        )
+        .await;
+        let tree = container.tree().unwrap();
+        let children = tree.get(&1).unwrap().children();
+        assert_eq!(children.map(|node| *node.key()).collect::<Vec<_>>(), [2]);
+        assert_eq!(flat_ids(&container), [(1, 0)]);
+    }
+
+    #[tokio::test]
+    async fn expansion_survives_a_refresh() {
+        let fake = Arc::new(FakeDatabase::default());
+        let mut container = container(&fake);
+        let tasks = [task::model(1, None), task::model(2, Some(1))];
+        query(&mut container, &fake, &tasks).await;
+        assert!(container.expand(1));
+        assert!(!container.expand(1u8));
+
+        query(
+            &mut container,
+            &fake,
+            &[tasks[0].clone(), tasks[1].clone(), task::model(3, Some(1))],
+        )
+        .await;
+        assert_eq!(flat_ids(&container), [(1, 0), (2, 1), (3, 1)]);
+    }
+}
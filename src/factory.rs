@@ -1,13 +1,17 @@
 use sea_orm::DatabaseConnection;
 use tokio::sync::mpsc;
 
-use crate::{container::builder::ContainerBuilder, messenger::ContainerData};
+use crate::{
+    carrier::shared_query::SharedQueries, container::builder::ContainerBuilder,
+    messenger::ContainerData,
+};
 
 pub struct Factory {
     pool: DatabaseConnection,
     all_tables: Vec<String>,
     tables_changed_sender: mpsc::Sender<Vec<String>>,
     new_register_sender: mpsc::Sender<ContainerData>,
+    shared_queries: SharedQueries,
 }
 
 impl Clone for Factory {
@@ -17,6 +21,7 @@ impl Clone for Factory {
             all_tables: self.all_tables.clone(),
             tables_changed_sender: self.tables_changed_sender.clone(),
             new_register_sender: self.new_register_sender.clone(),
+            shared_queries: self.shared_queries.clone(),
         }
     }
 }
@@ -27,12 +32,14 @@ impl Factory {
         all_tables: Vec<String>,
         tables_changed_sender: mpsc::Sender<Vec<String>>,
         new_register_sender: mpsc::Sender<ContainerData>,
+        shared_queries: SharedQueries,
     ) -> Self {
         Self {
             pool,
             all_tables,
             tables_changed_sender,
             new_register_sender,
+            shared_queries,
         }
     }
 
@@ -42,6 +49,7 @@ impl Factory {
             self.all_tables.clone(),
             self.tables_changed_sender.clone(),
             self.new_register_sender.clone(),
+            self.shared_queries.clone(),
         )
     }
 }
@@ -1,13 +1,13 @@
-use std::{collections::HashSet, fmt::Debug};
+use std::collections::HashSet;
 
 #[cfg(any(feature = "psql", feature = "mysql", feature = "sqlite"))]
 use chrono::{DateTime, FixedOffset, Local};
 #[cfg(any(feature = "psql", feature = "mysql", feature = "sqlite"))]
-use sea_orm::{EntityTrait, QuerySelect, Select};
+use sea_orm::{ConnectionTrait, DbErr, EntityTrait, ExecResult, QuerySelect, Select};
 use tracing::error;
 
 #[cfg(any(feature = "psql", feature = "mysql", feature = "sqlite"))]
-use crate::consts::QUERY_BUILDER;
+use crate::consts::{DB_BACKEND, QUERY_BUILDER};
 
 pub mod container;
 
@@ -21,6 +21,8 @@ mod consts;
 pub mod factory;
 #[cfg(any(feature = "psql", feature = "mysql", feature = "sqlite"))]
 pub mod messenger;
+#[cfg(all(test, any(feature = "psql", feature = "mysql", feature = "sqlite")))]
+mod test_db;
 
 fn get_tables_present(all_tables: &[String], query: &str) -> Vec<String> {
     all_tables
@@ -66,6 +68,25 @@ impl TablesCollector {
         let found_tables = get_tables_present(&self.all_tables, query);
         self.tables.extend(found_tables);
     }
+
+    /// Executes `execute` on `db` and adds the tables it touches once it
+    /// succeeded. Statements executed on `db` directly are not tracked, so
+    /// their tables are not invalidated.
+    #[cfg(any(feature = "psql", feature = "mysql", feature = "sqlite"))]
+    pub async fn execute<C>(
+        &mut self,
+        db: &C,
+        execute: impl sea_orm::QueryTrait,
+    ) -> Result<ExecResult, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        let statement = sea_orm::QueryTrait::build(&execute, DB_BACKEND);
+        let sql = statement.to_string();
+        let result = db.execute(statement).await?;
+        self.add(&sql);
+        Ok(result)
+    }
 }
 
 pub trait ToActiveModel {
@@ -103,23 +124,10 @@ where
     fn from_entity(entity: Entity) -> Self;
 }
 
-pub(crate) trait LogDebugErr {
-    fn log_err(self);
-}
-
 pub(crate) trait LogErr {
     fn log_msg(self, msg: &str);
 }
 
-impl<T, E> LogDebugErr for Result<T, E>
-where
-    E: Debug,
-{
-    fn log_err(self) {
-        let _ = self.map_err(|err| error!("error: {err:?}"));
-    }
-}
-
 impl<T, E> LogErr for Result<T, E> {
     fn log_msg(self, msg: &str) {
         let _ = self.map_err(|_| error!(msg));
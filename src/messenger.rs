@@ -1,9 +1,14 @@
 use std::collections::HashSet;
 
-use crate::{consts::DB_BACKEND, container::builder::ContainerBuilder, factory::Factory};
-use chrono::{DateTime, FixedOffset, Local};
+use crate::{
+    carrier::shared_query::SharedQueries,
+    consts::{quote_table, DB_BACKEND},
+    container::builder::ContainerBuilder,
+    factory::Factory,
+};
+use chrono::{DateTime, Duration, FixedOffset, Local};
 use sea_orm::{ConnectionTrait, DatabaseConnection, Statement};
-use tokio::sync::mpsc::{self};
+use tokio::sync::mpsc::{self, error::TrySendError};
 
 pub struct Messenger {
     db: DatabaseConnection,
@@ -14,6 +19,8 @@ pub struct Messenger {
     container_data: Vec<ContainerData>,
     new_register_reciver: mpsc::Receiver<ContainerData>,
     new_register_sender: mpsc::Sender<ContainerData>,
+
+    shared_queries: SharedQueries,
 }
 
 impl Messenger {
@@ -29,7 +36,7 @@ impl Messenger {
             .await
             .unwrap()
             .into_iter()
-            .map(|result| format!("\"{}\"", result.try_get::<String>("", "name").unwrap()))
+            .map(|result| quote_table(&result.try_get::<String>("", "name").unwrap()))
             .collect();
 
         Self {
@@ -40,6 +47,7 @@ impl Messenger {
             container_data: vec![],
             new_register_reciver,
             new_register_sender,
+            shared_queries: SharedQueries::default(),
         }
     }
 
@@ -49,32 +57,53 @@ impl Messenger {
             self.all_tables.clone(),
             self.tables_changed_sender.clone(),
             self.new_register_sender.clone(),
+            self.shared_queries.clone(),
         )
     }
 
+    /// Enables caching of query results for all containers of this messenger.
+    ///
+    /// Containers issuing a query that was already answered within the `ttl`
+    /// get the cached result without touching the database. Results are
+    /// evicted as soon as one of their tables changes, and the oldest ones
+    /// once more than `memory_budget` bytes would be cached in total. The
+    /// memory of a result is estimated from the size of its rows, anything
+    /// they allocate on the heap like strings is not counted.
+    pub fn enable_query_cache(&mut self, ttl: Duration, memory_budget: usize) {
+        self.shared_queries.enable_cache(ttl, memory_budget);
+    }
+
+    pub fn disable_query_cache(&mut self) {
+        self.shared_queries.disable_cache();
+    }
+
     pub fn factory(&self) -> Factory {
         Factory::new(
             self.db.clone(),
             self.all_tables.clone(),
             self.tables_changed_sender.clone(),
             self.new_register_sender.clone(),
+            self.shared_queries.clone(),
         )
     }
 
     pub fn state_update(&mut self) {
-        self.container_data
-            .iter_mut()
-            .for_each(ContainerData::try_recv_and_update);
+        self.container_data.retain_mut(|container| {
+            container.try_recv_and_update();
+            container.flush_pending_change()
+        });
 
         let mut changed_tables = HashSet::new();
         while let Ok(tables) = self.tables_changed.try_recv() {
             changed_tables.extend(tables);
         }
         let changed_tables = changed_tables.into_iter().collect::<Vec<_>>();
-        self.container_data
-            .iter_mut()
-            .filter(|container| container.is_interested(changed_tables.as_slice()))
-            .for_each(ContainerData::should_update);
+        if !changed_tables.is_empty() {
+            self.shared_queries.invalidate(&changed_tables);
+        }
+        self.container_data.retain_mut(|container| {
+            !container.is_interested(changed_tables.as_slice()) || container.should_update()
+        });
 
         while let Ok(data) = self.new_register_reciver.try_recv() {
             self.container_data.push(data);
@@ -86,6 +115,7 @@ pub struct ContainerData {
     tables_interested: Vec<String>,
     update_reciver: mpsc::Receiver<Vec<String>>,
     time_of_change_sender: mpsc::Sender<DateTime<FixedOffset>>,
+    pending_change: Option<DateTime<FixedOffset>>,
 }
 
 impl ContainerData {
@@ -97,6 +127,7 @@ impl ContainerData {
             tables_interested: vec![],
             update_reciver,
             time_of_change_sender: should_update_sender,
+            pending_change: None,
         }
     }
 
@@ -114,11 +145,135 @@ impl ContainerData {
             .any(|table| self.tables_interested.contains(table))
     }
 
-    /// Tells the container to query again since the values might have changed
-    fn should_update(&mut self) {
-        let now = Local::now().into();
-        if let Err(err) = self.time_of_change_sender.try_send(now) {
-            unreachable!("{}", err)
+    /// Tells the container to query again since the values might have changed.
+    /// Returns false once the container was dropped and should be unregistered.
+    fn should_update(&mut self) -> bool {
+        self.notify(Local::now().into())
+    }
+
+    /// Retries a change that didn't fit into the channel earlier. Returns false
+    /// once the container was dropped and should be unregistered.
+    fn flush_pending_change(&mut self) -> bool {
+        match self.pending_change.take() {
+            Some(time_of_change) => self.notify(time_of_change),
+            None => !self.time_of_change_sender.is_closed(),
         }
     }
+
+    /// Sends the change, if the channel is full because the container isn't
+    /// updated right now the change is kept and coalesced with later ones.
+    fn notify(&mut self, time_of_change: DateTime<FixedOffset>) -> bool {
+        match self.time_of_change_sender.try_send(time_of_change) {
+            Ok(()) => true,
+            Err(TrySendError::Full(time_of_change)) => {
+                self.pending_change = self.pending_change.max(Some(time_of_change));
+                true
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use sea_orm::ProxyRow;
+    use tokio::{sync::mpsc, task::yield_now};
+
+    use super::{ContainerData, Messenger};
+    use crate::{
+        consts::quote_table,
+        test_db::{self, FakeDatabase},
+    };
+
+    fn container_data(
+        capacity: usize,
+    ) -> (
+        ContainerData,
+        mpsc::Receiver<chrono::DateTime<chrono::FixedOffset>>,
+    ) {
+        let (_, update_reciver) = mpsc::channel(1);
+        let (time_of_change_sender, time_of_change_reciver) = mpsc::channel(capacity);
+        (
+            ContainerData::new(update_reciver, time_of_change_sender),
+            time_of_change_reciver,
+        )
+    }
+
+    #[test]
+    fn full_channel_coalesces_changes() {
+        let (mut data, mut reciver) = container_data(1);
+        assert!(data.should_update());
+        assert!(data.should_update());
+        let last_change = data.pending_change.expect("change should be pending");
+        assert!(data.should_update());
+        assert!(data.pending_change.unwrap() >= last_change);
+
+        assert!(reciver.try_recv().is_ok());
+        assert!(data.flush_pending_change());
+        assert!(data.pending_change.is_none());
+        assert!(reciver.try_recv().is_ok());
+        assert!(reciver.try_recv().is_err());
+    }
+
+    #[test]
+    fn dropped_container_is_unregistered() {
+        let (mut data, reciver) = container_data(1);
+        assert!(data.flush_pending_change());
+        drop(reciver);
+        assert!(!data.flush_pending_change());
+        assert!(!data.should_update());
+    }
+
+    #[tokio::test]
+    async fn tasked_container_refreshes_when_its_table_changes() {
+        let fake = Arc::new(FakeDatabase::default());
+        let tables = ["project", "task"]
+            .map(|name| ProxyRow::new(BTreeMap::from([("name".to_string(), name.into())])));
+        fake.push_query(Ok(tables.to_vec()));
+        let mut messenger = Messenger::new(test_db::connect(&fake)).await;
+
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let counter = fetches.clone();
+        let mut container = messenger.builder().tasked(
+            move |_: ()| {
+                let fetch = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                async move { Ok::<_, ()>(vec![fetch]) }
+            },
+            (),
+            &["task"],
+        );
+        let mut update = async |messenger: &mut Messenger| {
+            yield_now().await;
+            messenger.state_update();
+            container.state_update();
+            yield_now().await;
+            container.state_update();
+        };
+        update(&mut messenger).await;
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        messenger
+            .tables_changed_sender
+            .send(vec![quote_table("project")])
+            .await
+            .unwrap();
+        update(&mut messenger).await;
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        messenger
+            .tables_changed_sender
+            .send(vec![quote_table("task")])
+            .await
+            .unwrap();
+        update(&mut messenger).await;
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
 }
@@ -0,0 +1,202 @@
+//! A fake database and entities for tests, answering queries from a queue
+//! instead of connecting anywhere.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use sea_orm::{
+    DatabaseConnection, DbErr, EntityTrait, IdenStatic, Iterable, ModelTrait,
+    ProxyDatabaseConnector, ProxyDatabaseTrait, ProxyExecResult, ProxyRow, Statement,
+};
+
+use crate::consts::{quote_table, DB_BACKEND};
+
+/// Answers queries and statements with the queued results in order. Once a
+/// queue is empty queries return no rows and statements succeed.
+#[derive(Debug, Default)]
+pub(crate) struct FakeDatabase {
+    query_results: Mutex<VecDeque<Result<Vec<ProxyRow>, DbErr>>>,
+    execute_results: Mutex<VecDeque<Result<ProxyExecResult, DbErr>>>,
+    statements: Mutex<Vec<String>>,
+}
+
+impl FakeDatabase {
+    pub fn push_query(&self, result: Result<Vec<ProxyRow>, DbErr>) {
+        self.query_results.lock().unwrap().push_back(result);
+    }
+
+    pub fn push_execute(&self, result: Result<ProxyExecResult, DbErr>) {
+        self.execute_results.lock().unwrap().push_back(result);
+    }
+
+    /// The sql of all queries and statements so far
+    pub fn statements(&self) -> Vec<String> {
+        self.statements.lock().unwrap().clone()
+    }
+}
+
+#[derive(Debug)]
+struct FakeProxy(Arc<FakeDatabase>);
+
+#[async_trait::async_trait]
+impl ProxyDatabaseTrait for FakeProxy {
+    async fn query(&self, statement: Statement) -> Result<Vec<ProxyRow>, DbErr> {
+        self.0
+            .statements
+            .lock()
+            .unwrap()
+            .push(statement.to_string());
+        let result = self.0.query_results.lock().unwrap().pop_front();
+        result.unwrap_or_else(|| Ok(vec![]))
+    }
+
+    async fn execute(&self, statement: Statement) -> Result<ProxyExecResult, DbErr> {
+        self.0
+            .statements
+            .lock()
+            .unwrap()
+            .push(statement.to_string());
+        let result = self.0.execute_results.lock().unwrap().pop_front();
+        result.unwrap_or_else(|| Ok(ProxyExecResult::new(0, 1)))
+    }
+}
+
+pub(crate) fn connect(fake: &Arc<FakeDatabase>) -> DatabaseConnection {
+    let proxy: Box<dyn ProxyDatabaseTrait> = Box::new(FakeProxy(fake.clone()));
+    ProxyDatabaseConnector::connect(DB_BACKEND, Arc::new(proxy)).unwrap()
+}
+
+/// The row the database returns for `model`
+pub(crate) fn row<M>(model: &M) -> ProxyRow
+where
+    M: ModelTrait,
+{
+    prefixed_row(model, "")
+}
+
+/// The row of `model` with every column prefixed, as in selects of
+/// several entities
+pub(crate) fn prefixed_row<M>(model: &M, prefix: &str) -> ProxyRow
+where
+    M: ModelTrait,
+{
+    let values = <M::Entity as EntityTrait>::Column::iter()
+        .map(|column| (format!("{prefix}{}", column.as_str()), model.get(column)))
+        .collect::<BTreeMap<_, _>>();
+    ProxyRow::new(values)
+}
+
+pub(crate) mod project {
+    use sea_orm::entity::prelude::*;
+
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+    #[sea_orm(table_name = "project")]
+    pub struct Model {
+        #[sea_orm(primary_key)]
+        pub id: i32,
+        pub name: String,
+    }
+
+    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+    pub enum Relation {
+        #[sea_orm(has_many = "super::task::Entity")]
+        Task,
+    }
+
+    impl Related<super::task::Entity> for Entity {
+        fn to() -> RelationDef {
+            Relation::Task.def()
+        }
+    }
+
+    impl ActiveModelBehavior for ActiveModel {}
+
+    pub fn model(id: i32) -> Model {
+        Model {
+            id,
+            name: format!("project {id}"),
+        }
+    }
+}
+
+pub(crate) mod task {
+    use sea_orm::entity::prelude::*;
+
+    use crate::{FromEntity, ToEntity};
+
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+    #[sea_orm(table_name = "task")]
+    pub struct Model {
+        #[sea_orm(primary_key)]
+        pub id: i32,
+        pub project_id: Option<i32>,
+        pub title: String,
+    }
+
+    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+    pub enum Relation {
+        #[sea_orm(
+            belongs_to = "super::project::Entity",
+            from = "Column::ProjectId",
+            to = "super::project::Column::Id"
+        )]
+        Project,
+    }
+
+    impl Related<super::project::Entity> for Entity {
+        fn to() -> RelationDef {
+            Relation::Project.def()
+        }
+    }
+
+    impl ActiveModelBehavior for ActiveModel {}
+
+    pub fn model(id: i32, project_id: Option<i32>) -> Model {
+        Model {
+            id,
+            project_id,
+            title: format!("task {id}"),
+        }
+    }
+
+    /// How containers show a task
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Task {
+        pub id: i32,
+        pub project_id: Option<i32>,
+        pub title: String,
+    }
+
+    impl ToEntity<Task> for Model {
+        fn to_entity(self) -> Task {
+            Task {
+                id: self.id,
+                project_id: self.project_id,
+                title: self.title,
+            }
+        }
+    }
+
+    impl FromEntity<Task> for Model {
+        fn from_entity(task: Task) -> Self {
+            Self {
+                id: task.id,
+                project_id: task.project_id,
+                title: task.title,
+            }
+        }
+    }
+
+    crate::impl_to_active_model!(Task, Model);
+}
+
+/// The names of all tables of the test entities, quoted as the backend
+/// quotes them in queries
+pub(crate) fn all_tables() -> Vec<String> {
+    ["project", "task"]
+        .iter()
+        .map(|table| quote_table(table))
+        .collect()
+}